@@ -1,5 +1,3 @@
-use gcc;
-
 fn main() {
     // 1 - Add a search path for compiled library
     println!("cargo:rustc-link-search=./src/clib");
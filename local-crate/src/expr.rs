@@ -0,0 +1,378 @@
+//! Integer arithmetic expressions, shared by `lib::run` and `const_eval!`.
+//!
+//! ```text
+//! expr    := term (("+" | "-") term)*
+//! term    := unary (("*" | "/" | "%") unary)*
+//! unary   := "-" unary | primary
+//! primary := number | "(" expr ")"
+//! number  := decimal digits, optionally separated by "_"
+//! ```
+//!
+//! The parser works on tokens carrying any kind of span, so that callers
+//! with their own tokenizer can report errors at their own locations.
+
+use std::error;
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Number(i64),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Open,
+    Close,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token<S> {
+    pub kind: TokenKind,
+    pub span: S,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr<S> {
+    Number(i64),
+    /// The span is the one of the operator
+    Negate(Box<Expr<S>>, S),
+    /// The span is the one of the operator
+    Binary(BinaryOperator, Box<Expr<S>>, Box<Expr<S>>, S),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    UnexpectedCharacter,
+    UnexpectedToken,
+    UnexpectedEnd,
+    InvalidNumber,
+    Overflow,
+    DivisionByZero,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error<S> {
+    pub kind: ErrorKind,
+    pub span: S,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            ErrorKind::UnexpectedCharacter => "unexpected character",
+            ErrorKind::UnexpectedToken => "unexpected token",
+            ErrorKind::UnexpectedEnd => "unexpected end of expression",
+            ErrorKind::InvalidNumber => "invalid number",
+            ErrorKind::Overflow => "arithmetic overflow",
+            ErrorKind::DivisionByZero => "division by zero",
+        };
+        f.write_str(message)
+    }
+}
+
+impl fmt::Display for Error<Range<usize>> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}..{}", self.kind, self.span.start, self.span.end)
+    }
+}
+
+impl error::Error for Error<Range<usize>> {}
+
+// ____________________________________________________________
+// Tokenizer
+
+/// Parses a decimal literal such as `1_000`.
+pub fn parse_number(text: &str) -> Result<i64, ErrorKind> {
+    let valid = text.starts_with(|c: char| c.is_ascii_digit())
+        && text.chars().all(|c| c.is_ascii_digit() || c == '_');
+    if !valid {
+        return Err(ErrorKind::InvalidNumber);
+    }
+    text.chars()
+        .filter(|c| *c != '_')
+        .try_fold(0i64, |value, digit| {
+            value
+                .checked_mul(10)?
+                .checked_add(i64::from(digit.to_digit(10)?))
+        })
+        .ok_or(ErrorKind::Overflow)
+}
+
+/// Splits `source` into tokens spanning byte ranges.
+pub fn tokenize(source: &str) -> Result<Vec<Token<Range<usize>>>, Error<Range<usize>>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let mut end = start + c.len_utf8();
+        let kind = match c {
+            c if c.is_whitespace() => continue,
+            '+' => TokenKind::Plus,
+            '-' => TokenKind::Minus,
+            '*' => TokenKind::Star,
+            '/' => TokenKind::Slash,
+            '%' => TokenKind::Percent,
+            '(' => TokenKind::Open,
+            ')' => TokenKind::Close,
+            c if c.is_ascii_digit() => {
+                // Consumes trailing letters too, so that `12a` is one invalid number
+                while let Some(&(index, c)) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    end = index + c.len_utf8();
+                    chars.next();
+                }
+                let value = parse_number(&source[start..end]).map_err(|kind| Error {
+                    kind,
+                    span: start..end,
+                })?;
+                TokenKind::Number(value)
+            }
+            _ => {
+                return Err(Error {
+                    kind: ErrorKind::UnexpectedCharacter,
+                    span: start..end,
+                })
+            }
+        };
+        tokens.push(Token {
+            kind,
+            span: start..end,
+        });
+    }
+    Ok(tokens)
+}
+
+// ____________________________________________________________
+// Parser
+
+struct Parser<'a, S> {
+    tokens: &'a [Token<S>],
+    position: usize,
+    end: S,
+}
+
+impl<'a, S: Clone> Parser<'a, S> {
+    fn peek(&self) -> Option<&'a Token<S>> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<&'a Token<S>, Error<S>> {
+        let token = self.peek().ok_or_else(|| Error {
+            kind: ErrorKind::UnexpectedEnd,
+            span: self.end.clone(),
+        })?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn binary(
+        &mut self,
+        operand: fn(&mut Self) -> Result<Expr<S>, Error<S>>,
+        operator: fn(TokenKind) -> Option<BinaryOperator>,
+    ) -> Result<Expr<S>, Error<S>> {
+        let mut left = operand(self)?;
+        while let Some(token) = self.peek() {
+            let Some(op) = operator(token.kind) else {
+                break;
+            };
+            self.position += 1;
+            let right = operand(self)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right), token.span.clone());
+        }
+        Ok(left)
+    }
+
+    fn expr(&mut self) -> Result<Expr<S>, Error<S>> {
+        self.binary(Self::term, |kind| match kind {
+            TokenKind::Plus => Some(BinaryOperator::Add),
+            TokenKind::Minus => Some(BinaryOperator::Subtract),
+            _ => None,
+        })
+    }
+
+    fn term(&mut self) -> Result<Expr<S>, Error<S>> {
+        self.binary(Self::unary, |kind| match kind {
+            TokenKind::Star => Some(BinaryOperator::Multiply),
+            TokenKind::Slash => Some(BinaryOperator::Divide),
+            TokenKind::Percent => Some(BinaryOperator::Remainder),
+            _ => None,
+        })
+    }
+
+    fn unary(&mut self) -> Result<Expr<S>, Error<S>> {
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::Minus => Ok(Expr::Negate(Box::new(self.unary()?), token.span.clone())),
+            TokenKind::Number(value) => Ok(Expr::Number(value)),
+            TokenKind::Open => {
+                let expr = self.expr()?;
+                let close = self.next()?;
+                match close.kind {
+                    TokenKind::Close => Ok(expr),
+                    _ => Err(Error {
+                        kind: ErrorKind::UnexpectedToken,
+                        span: close.span.clone(),
+                    }),
+                }
+            }
+            _ => Err(Error {
+                kind: ErrorKind::UnexpectedToken,
+                span: token.span.clone(),
+            }),
+        }
+    }
+}
+
+/// Parses all `tokens`, `end` is the span reported when they end too early.
+pub fn parse<S: Clone>(tokens: &[Token<S>], end: S) -> Result<Expr<S>, Error<S>> {
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        end,
+    };
+    let expr = parser.expr()?;
+    match parser.peek() {
+        Some(token) => Err(Error {
+            kind: ErrorKind::UnexpectedToken,
+            span: token.span.clone(),
+        }),
+        None => Ok(expr),
+    }
+}
+
+// ____________________________________________________________
+// Evaluation
+
+impl<S: Clone> Expr<S> {
+    /// Errors point at the operator that overflowed or divided by zero.
+    pub fn evaluate(&self) -> Result<i64, Error<S>> {
+        let error = |kind, span: &S| Error {
+            kind,
+            span: span.clone(),
+        };
+        match self {
+            Expr::Number(value) => Ok(*value),
+            Expr::Negate(operand, span) => operand
+                .evaluate()?
+                .checked_neg()
+                .ok_or_else(|| error(ErrorKind::Overflow, span)),
+            Expr::Binary(op, left, right, span) => {
+                let (left, right) = (left.evaluate()?, right.evaluate()?);
+                let result = match op {
+                    BinaryOperator::Add => left.checked_add(right),
+                    BinaryOperator::Subtract => left.checked_sub(right),
+                    BinaryOperator::Multiply => left.checked_mul(right),
+                    BinaryOperator::Divide | BinaryOperator::Remainder if right == 0 => {
+                        return Err(error(ErrorKind::DivisionByZero, span))
+                    }
+                    BinaryOperator::Divide => left.checked_div(right),
+                    BinaryOperator::Remainder => left.checked_rem(right),
+                };
+                result.ok_or_else(|| error(ErrorKind::Overflow, span))
+            }
+        }
+    }
+}
+
+/// Tokenizes, parses and evaluates `source`.
+///
+/// # Examples
+/// ```
+/// use local_crate::expr::{evaluate, ErrorKind};
+///
+/// assert_eq!(evaluate("-(1 + 2) * 3"), Ok(-9));
+/// assert_eq!(evaluate("1 / (2 - 2)").unwrap_err().kind, ErrorKind::DivisionByZero);
+/// ```
+pub fn evaluate(source: &str) -> Result<i64, Error<Range<usize>>> {
+    let tokens = tokenize(source)?;
+    parse(&tokens, source.len()..source.len())?.evaluate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        assert_eq!(evaluate("42"), Ok(42));
+        assert_eq!(evaluate("1_000 + 2 * 3"), Ok(1006));
+        assert_eq!(evaluate("(1 + 2) * 3"), Ok(9));
+        assert_eq!(evaluate("10 - 4 - 3"), Ok(3));
+        assert_eq!(evaluate("-7 / 2"), Ok(-3));
+        assert_eq!(evaluate("-7 % 2"), Ok(-1));
+        assert_eq!(evaluate("--1"), Ok(1));
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |source| evaluate(source).unwrap_err();
+        assert_eq!(
+            error("1 +"),
+            Error {
+                kind: ErrorKind::UnexpectedEnd,
+                span: 3..3
+            }
+        );
+        assert_eq!(
+            error("1 2"),
+            Error {
+                kind: ErrorKind::UnexpectedToken,
+                span: 2..3
+            }
+        );
+        assert_eq!(
+            error("(1"),
+            Error {
+                kind: ErrorKind::UnexpectedEnd,
+                span: 2..2
+            }
+        );
+        assert_eq!(
+            error("1 $"),
+            Error {
+                kind: ErrorKind::UnexpectedCharacter,
+                span: 2..3
+            }
+        );
+        assert_eq!(
+            error("12a"),
+            Error {
+                kind: ErrorKind::InvalidNumber,
+                span: 0..3
+            }
+        );
+        assert_eq!(
+            error("99999999999999999999"),
+            Error {
+                kind: ErrorKind::Overflow,
+                span: 0..20
+            }
+        );
+        assert_eq!(
+            error("1 + 4 / 0"),
+            Error {
+                kind: ErrorKind::DivisionByZero,
+                span: 6..7
+            }
+        );
+        assert_eq!(
+            error("9223372036854775807 * 2"),
+            Error {
+                kind: ErrorKind::Overflow,
+                span: 20..21
+            }
+        );
+    }
+}
@@ -1,3 +1,5 @@
+pub mod expr;
+
 pub fn get10() -> i32 {
     10
 }
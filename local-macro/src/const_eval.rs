@@ -0,0 +1,59 @@
+use local_crate::expr::{self, ErrorKind, Token, TokenKind};
+use proc_macro2::{Delimiter, Literal, Span, TokenStream, TokenTree};
+use quote::quote;
+
+pub fn expand(input: TokenStream) -> syn::Result<TokenStream> {
+    let mut tokens = Vec::new();
+    tokenize(input, &mut tokens)?;
+    let value = expr::parse(&tokens, Span::call_site())
+        .and_then(|expr| expr.evaluate())
+        .map_err(|error| syn::Error::new(error.span, error.kind))?;
+
+    let literal = Literal::u64_unsuffixed(value.unsigned_abs());
+    Ok(if value < 0 {
+        quote!((-#literal))
+    } else {
+        quote!(#literal)
+    })
+}
+
+fn tokenize(input: TokenStream, tokens: &mut Vec<Token<Span>>) -> syn::Result<()> {
+    for tree in input {
+        let kind = match &tree {
+            TokenTree::Punct(punct) => match punct.as_char() {
+                '+' => TokenKind::Plus,
+                '-' => TokenKind::Minus,
+                '*' => TokenKind::Star,
+                '/' => TokenKind::Slash,
+                '%' => TokenKind::Percent,
+                _ => return Err(syn::Error::new(punct.span(), ErrorKind::UnexpectedToken)),
+            },
+            TokenTree::Literal(literal) => match expr::parse_number(&literal.to_string()) {
+                Ok(value) => TokenKind::Number(value),
+                Err(kind) => return Err(syn::Error::new(literal.span(), kind)),
+            },
+            // Expressions passed through `macro_rules!` arrive in invisible
+            // groups, which keep their precedence as parentheses do
+            TokenTree::Group(group)
+                if matches!(group.delimiter(), Delimiter::Parenthesis | Delimiter::None) =>
+            {
+                tokens.push(Token {
+                    kind: TokenKind::Open,
+                    span: group.span_open(),
+                });
+                tokenize(group.stream(), tokens)?;
+                tokens.push(Token {
+                    kind: TokenKind::Close,
+                    span: group.span_close(),
+                });
+                continue;
+            }
+            tree => return Err(syn::Error::new(tree.span(), ErrorKind::UnexpectedToken)),
+        };
+        tokens.push(Token {
+            kind,
+            span: tree.span(),
+        });
+    }
+    Ok(())
+}
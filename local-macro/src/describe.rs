@@ -0,0 +1,131 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Data, DataEnum, DeriveInput, Fields, Member, Meta, Token};
+
+pub fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    let (kind, tag, fields, variants) = match &input.data {
+        Data::Struct(data) => {
+            let fields = fields(&data.fields, true);
+            (quote!(Struct), quote!(None), fields, Vec::new())
+        }
+        Data::Enum(data) => {
+            let variants = data
+                .variants
+                .iter()
+                .map(|variant| {
+                    let name = variant.ident.to_string();
+                    let fields = fields(&variant.fields, false);
+                    quote! {
+                        ::lib::describe::VariantDescription {
+                            name: #name,
+                            fields: ::std::vec![#(#fields),*],
+                        }
+                    }
+                })
+                .collect();
+            let tag = match tag(&input.attrs, data)? {
+                Some(size) => quote!(Some(0..#size)),
+                None => quote!(None),
+            };
+            (quote!(Enum), tag, Vec::new(), variants)
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span,
+                "Describe cannot be derived for unions",
+            ))
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::lib::describe::Describe for #name #type_generics #where_clause {
+            fn describe() -> ::lib::describe::TypeDescription {
+                ::lib::describe::TypeDescription {
+                    name: ::std::any::type_name::<Self>(),
+                    kind: ::lib::describe::TypeKind::#kind,
+                    size: ::std::mem::size_of::<Self>(),
+                    align: ::std::mem::align_of::<Self>(),
+                    niche: ::std::mem::size_of::<::std::option::Option<Self>>()
+                        == ::std::mem::size_of::<Self>(),
+                    tag: ::std::option::Option::#tag,
+                    fields: ::std::vec![#(#fields),*],
+                    variants: ::std::vec![#(#variants),*],
+                }
+            }
+        }
+    })
+}
+
+/// The size of the tag of an enum, at offset 0: the whole enum when all
+/// variants are units, else the integer of its `repr`.
+fn tag(attrs: &[Attribute], data: &DataEnum) -> syn::Result<Option<TokenStream>> {
+    if data
+        .variants
+        .iter()
+        .all(|variant| variant.fields.is_empty())
+    {
+        return Ok(Some(quote!(::std::mem::size_of::<Self>())));
+    }
+    let mut tag = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("repr")) {
+        let reprs = attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+        for repr in reprs {
+            let Meta::Path(path) = repr else {
+                continue;
+            };
+            let Some(ident) = path.get_ident() else {
+                continue;
+            };
+            match ident.to_string().as_str() {
+                "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32" | "i64"
+                | "i128" | "isize" => tag = Some(quote!(::std::mem::size_of::<#ident>())),
+                // `repr(C, u8)` has a `u8` tag
+                "C" if tag.is_none() => {
+                    tag = Some(quote!(::std::mem::size_of::<::std::ffi::c_int>()))
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(tag)
+}
+
+// Offsets of enum variant fields are not available on stable
+fn fields(fields: &Fields, with_offsets: bool) -> Vec<TokenStream> {
+    fields
+        .members()
+        .zip(fields.iter())
+        .map(|(member, field)| {
+            let name = match &member {
+                Member::Named(ident) => ident.to_string(),
+                Member::Unnamed(index) => index.index.to_string(),
+            };
+            let ty = &field.ty;
+            let offset = if with_offsets {
+                quote!(::std::option::Option::Some(
+                    ::std::mem::offset_of!(Self, #member)
+                ))
+            } else {
+                quote!(::std::option::Option::None)
+            };
+            quote! {
+                ::lib::describe::FieldDescription {
+                    name: #name,
+                    type_name: ::std::any::type_name::<#ty>(),
+                    offset: #offset,
+                    size: ::std::mem::size_of::<#ty>(),
+                    niche: ::std::mem::size_of::<::std::option::Option<#ty>>()
+                        == ::std::mem::size_of::<#ty>(),
+                    niche_bytes: {
+                        use ::lib::describe::probe::{Described as _, Scalar as _, Unknown as _};
+                        (&&&::lib::describe::Probe::<#ty>(::std::marker::PhantomData)).niche_bytes()
+                    },
+                }
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,268 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, quote_spanned};
+use std::collections::HashMap;
+use syn::spanned::Spanned;
+use syn::{Attribute, Data, DeriveInput, Fields, Ident, LitStr, Member, Type};
+
+enum Display {
+    Format(LitStr),
+    Transparent(Span),
+}
+
+struct Field<'a> {
+    member: Member,
+    binding: Ident,
+    ty: &'a Type,
+    source: bool,
+    from: Option<Span>,
+}
+
+/// A struct or an enum variant.
+struct Shape<'a> {
+    /// `Self` or `Self::Variant`
+    path: TokenStream,
+    display: Display,
+    fields: Vec<Field<'a>>,
+    span: Span,
+}
+
+pub fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let shapes = match &input.data {
+        Data::Struct(data) => {
+            let display = display(&input.attrs, input.ident.span())?;
+            vec![shape(
+                quote!(Self),
+                display,
+                &data.fields,
+                input.ident.span(),
+            )?]
+        }
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|variant| {
+                let name = &variant.ident;
+                let display = display(&variant.attrs, name.span())?;
+                shape(quote!(Self::#name), display, &variant.fields, name.span())
+            })
+            .collect::<syn::Result<_>>()?,
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span,
+                "Error cannot be derived for unions",
+            ))
+        }
+    };
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut display_arms = Vec::new();
+    let mut source_arms = Vec::new();
+    let mut from_impls = Vec::new();
+    let mut from_types = HashMap::new();
+    for shape in &shapes {
+        let path = &shape.path;
+        let pattern = pattern(shape);
+
+        let body = match &shape.display {
+            Display::Format(format) => {
+                let format = rewrite_positional(format);
+                quote!(::std::write!(__formatter, #format))
+            }
+            Display::Transparent(_) => {
+                let binding = &shape.fields[0].binding;
+                quote!(::std::fmt::Display::fmt(#binding, __formatter))
+            }
+        };
+        display_arms.push(quote!(#pattern => #body,));
+
+        let source = match &shape.display {
+            Display::Transparent(_) => {
+                let binding = &shape.fields[0].binding;
+                quote!(#binding.as_dyn_error().source())
+            }
+            Display::Format(_) => match shape.fields.iter().find(|field| field.source) {
+                Some(field) => {
+                    let binding = &field.binding;
+                    quote!(::std::option::Option::Some(#binding.as_dyn_error()))
+                }
+                None => quote!(::std::option::Option::None),
+            },
+        };
+        source_arms.push(quote!(#pattern => #source,));
+
+        let Some(field) = shape.fields.iter().find(|field| field.from.is_some()) else {
+            continue;
+        };
+        let from_span = field.from.unwrap();
+        let ty = field.ty;
+        let key = quote!(#ty).to_string();
+        if from_types.insert(key, from_span).is_some() {
+            return Err(syn::Error::new(
+                from_span,
+                format!(
+                    "conflicting #[from] for `{}`, it is already used",
+                    quote!(#ty)
+                ),
+            ));
+        }
+        let member = &field.member;
+        from_impls.push(quote! {
+            impl #impl_generics ::std::convert::From<#ty> for #name #type_generics #where_clause {
+                fn from(source: #ty) -> Self {
+                    #path { #member: source }
+                }
+            }
+        });
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::std::fmt::Display for #name #type_generics #where_clause {
+            #[allow(unused_variables)]
+            fn fmt(&self, __formatter: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                match self {
+                    #(#display_arms)*
+                }
+            }
+        }
+
+        impl #impl_generics ::std::error::Error for #name #type_generics #where_clause {
+            #[allow(unused_variables)]
+            fn source(&self) -> ::std::option::Option<&(dyn ::std::error::Error + 'static)> {
+                use ::lib::error::AsDynError as _;
+                match self {
+                    #(#source_arms)*
+                }
+            }
+        }
+
+        #(#from_impls)*
+    })
+}
+
+fn display(attributes: &[Attribute], span: Span) -> syn::Result<Display> {
+    let mut display = None;
+    for attribute in attributes {
+        if !attribute.path().is_ident("error") {
+            continue;
+        }
+        if display.is_some() {
+            return Err(syn::Error::new(
+                attribute.span(),
+                "duplicate #[error] attribute",
+            ));
+        }
+        display = Some(attribute.parse_args_with(|input: syn::parse::ParseStream| {
+            if input.peek(Ident) {
+                let ident: Ident = input.parse()?;
+                if ident != "transparent" {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        "expected a format string or `transparent`",
+                    ));
+                }
+                Ok(Display::Transparent(ident.span()))
+            } else {
+                Ok(Display::Format(input.parse()?))
+            }
+        })?);
+    }
+    display.ok_or_else(|| {
+        syn::Error::new(
+            span,
+            "missing #[error(\"...\")] or #[error(transparent)] attribute",
+        )
+    })
+}
+
+fn shape<'a>(
+    path: TokenStream,
+    display: Display,
+    fields: &'a Fields,
+    span: Span,
+) -> syn::Result<Shape<'a>> {
+    let mut result = Vec::new();
+    for (index, (member, field)) in fields.members().zip(fields.iter()).enumerate() {
+        let binding = match &field.ident {
+            Some(ident) => ident.clone(),
+            None => format_ident!("__field{}", index),
+        };
+        let from = field
+            .attrs
+            .iter()
+            .find(|attribute| attribute.path().is_ident("from"))
+            .map(|attribute| attribute.span());
+        let source = from.is_some()
+            || field
+                .attrs
+                .iter()
+                .any(|attribute| attribute.path().is_ident("source"))
+            || field.ident.as_ref().is_some_and(|ident| ident == "source");
+        result.push(Field {
+            member,
+            binding,
+            ty: &field.ty,
+            source,
+            from,
+        });
+    }
+
+    let shape = Shape {
+        path,
+        display,
+        fields: result,
+        span,
+    };
+    if shape.fields.iter().filter(|field| field.source).count() > 1 {
+        return Err(syn::Error::new(shape.span, "multiple source fields"));
+    }
+    if let Some(from) = shape.fields.iter().find_map(|field| field.from) {
+        if shape.fields.len() != 1 {
+            return Err(syn::Error::new(from, "#[from] requires a single field"));
+        }
+    }
+    if let Display::Transparent(span) = shape.display {
+        if shape.fields.len() != 1 {
+            return Err(syn::Error::new(
+                span,
+                "#[error(transparent)] requires a single field",
+            ));
+        }
+    }
+    Ok(shape)
+}
+
+/// `Self::Variant { a, b }` or `Self::Variant { 0: __field0 }`, binding every field by reference.
+fn pattern(shape: &Shape) -> TokenStream {
+    let path = &shape.path;
+    let fields = shape.fields.iter().map(|field| {
+        let binding = &field.binding;
+        match &field.member {
+            Member::Named(_) => quote!(#binding),
+            Member::Unnamed(index) => quote!(#index: #binding),
+        }
+    });
+    quote!(#path { #(#fields),* })
+}
+
+/// Rewrites `{0}` as `{__field0}`, so that tuple fields can be interpolated
+/// as named fields are.
+fn rewrite_positional(format: &LitStr) -> TokenStream {
+    let value = format.value();
+    let mut rewritten = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        rewritten.push(c);
+        if c != '{' {
+            continue;
+        }
+        if chars.peek() == Some(&'{') {
+            rewritten.push(chars.next().unwrap());
+        } else if chars.peek().is_some_and(char::is_ascii_digit) {
+            rewritten.push_str("__field");
+        }
+    }
+    let literal = LitStr::new(&rewritten, format.span());
+    quote_spanned!(format.span()=> #literal)
+}
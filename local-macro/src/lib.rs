@@ -0,0 +1,65 @@
+//! Procedural macros re-exported by `lib`.
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput, ItemFn};
+
+mod const_eval;
+mod describe;
+mod error;
+mod stable_hash;
+mod trace;
+
+/// Implements `lib::Describe`, see its documentation.
+#[proc_macro_derive(Describe)]
+pub fn derive_describe(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    describe::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Implements `Display`, `std::error::Error` and `From`, see `lib::error`.
+#[proc_macro_derive(Error, attributes(error, source, from))]
+pub fn derive_error(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    error::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Implements `lib::hash::StableHash`, see its documentation.
+#[proc_macro_derive(StableHash)]
+pub fn derive_stable_hash(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    stable_hash::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Logs calls through `lib::trace`, see its documentation.
+#[proc_macro_attribute]
+pub fn trace(attribute: TokenStream, item: TokenStream) -> TokenStream {
+    if !attribute.is_empty() {
+        let error = syn::Error::new(
+            proc_macro2::TokenStream::from(attribute)
+                .into_iter()
+                .next()
+                .unwrap()
+                .span(),
+            "#[trace] does not take arguments",
+        );
+        return error.into_compile_error().into();
+    }
+    let function = parse_macro_input!(item as ItemFn);
+    trace::expand(function)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Evaluates an integer expression at compile time, see `lib::const_eval!`.
+#[proc_macro]
+pub fn const_eval(input: TokenStream) -> TokenStream {
+    const_eval::expand(input.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,77 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{parse_quote, Data, DeriveInput, Fields, GenericParam, Ident, Member};
+
+pub fn expand(mut input: DeriveInput) -> syn::Result<TokenStream> {
+    // As std derives, each type parameter must implement the trait
+    let parameters: Vec<Ident> = input
+        .generics
+        .params
+        .iter()
+        .filter_map(|parameter| match parameter {
+            GenericParam::Type(parameter) => Some(parameter.ident.clone()),
+            _ => None,
+        })
+        .collect();
+    let where_clause = input.generics.make_where_clause();
+    for parameter in parameters {
+        where_clause
+            .predicates
+            .push(parse_quote!(#parameter: ::lib::hash::StableHash));
+    }
+
+    let arms = match &input.data {
+        Data::Struct(data) => vec![arm(quote!(Self), None, &data.fields)],
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .enumerate()
+            .map(|(index, variant)| {
+                let name = &variant.ident;
+                let index = u32::try_from(index).unwrap();
+                arm(quote!(Self::#name), Some(index), &variant.fields)
+            })
+            .collect(),
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span,
+                "StableHash cannot be derived for unions",
+            ))
+        }
+    };
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::lib::hash::StableHash for #name #type_generics #where_clause {
+            fn stable_hash<__H: ::std::hash::Hasher>(&self, __state: &mut __H) {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+    })
+}
+
+/// Hashes the index of the variant if any, then the fields in declaration
+/// order, their names being left out so that renaming a field keeps digests.
+fn arm(path: TokenStream, variant: Option<u32>, fields: &Fields) -> TokenStream {
+    let (patterns, bindings): (Vec<TokenStream>, Vec<Ident>) = fields
+        .members()
+        .map(|member| match member {
+            Member::Named(ident) => (quote!(#ident), ident),
+            Member::Unnamed(index) => {
+                let binding = format_ident!("__field{}", index.index);
+                (quote!(#index: #binding), binding)
+            }
+        })
+        .unzip();
+    let variant =
+        variant.map(|index| quote!(::lib::hash::StableHash::stable_hash(&#index, __state);));
+    quote! {
+        #path { #(#patterns),* } => {
+            #variant
+            #(::lib::hash::StableHash::stable_hash(#bindings, __state);)*
+        }
+    }
+}
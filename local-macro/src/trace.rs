@@ -0,0 +1,91 @@
+use proc_macro2::{TokenStream, TokenTree};
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{FnArg, ItemFn, Pat, ReturnType, Type};
+
+pub fn expand(function: ItemFn) -> syn::Result<TokenStream> {
+    let signature = &function.sig;
+    if let Some(asyncness) = &signature.asyncness {
+        return Err(syn::Error::new(
+            asyncness.span(),
+            "#[trace] does not support async functions",
+        ));
+    }
+    if let Some(constness) = &signature.constness {
+        return Err(syn::Error::new(
+            constness.span(),
+            "#[trace] does not support const functions",
+        ));
+    }
+
+    let name = signature.ident.to_string();
+    let mut arguments = Vec::new();
+    for input in &signature.inputs {
+        let FnArg::Typed(argument) = input else {
+            continue;
+        };
+        let Pat::Ident(pattern) = &*argument.pat else {
+            return Err(syn::Error::new(
+                argument.pat.span(),
+                "#[trace] only supports arguments bound to an identifier",
+            ));
+        };
+        let ident = &pattern.ident;
+        let label = ident.to_string();
+        arguments.push(quote!((#label, &#ident as &dyn ::std::fmt::Debug)));
+    }
+
+    // `impl Trait` cannot be written as the return type of a closure
+    let (output_type, exit) = match &signature.output {
+        ReturnType::Default => (Some(quote!(())), quote!(exit)),
+        ReturnType::Type(_, ty) => {
+            let annotation = (!contains_impl_trait(ty)).then(|| quote!(#ty));
+            let exit = if is_result(ty) {
+                quote!(exit_result)
+            } else {
+                quote!(exit)
+            };
+            (annotation, exit)
+        }
+    };
+    let closure_output = output_type.as_ref().map(|ty| quote!(-> #ty));
+    let output_annotation = output_type.as_ref().map(|ty| quote!(: #ty));
+
+    let attributes = &function.attrs;
+    let visibility = &function.vis;
+    let block = &function.block;
+    Ok(quote! {
+        #(#attributes)*
+        #visibility #signature {
+            let __trace_call = ::lib::trace::Call::enter(#name, &[#(#arguments),*]);
+            #[allow(clippy::redundant_closure_call)]
+            let __trace_output #output_annotation = (move || #closure_output #block)();
+            __trace_call.#exit(&__trace_output);
+            __trace_output
+        }
+    })
+}
+
+fn is_result(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Result"),
+        Type::Paren(paren) => is_result(&paren.elem),
+        Type::Group(group) => is_result(&group.elem),
+        _ => false,
+    }
+}
+
+fn contains_impl_trait(ty: &Type) -> bool {
+    fn contains_impl(tokens: TokenStream) -> bool {
+        tokens.into_iter().any(|token| match token {
+            TokenTree::Ident(ident) => ident == "impl",
+            TokenTree::Group(group) => contains_impl(group.stream()),
+            _ => false,
+        })
+    }
+    contains_impl(quote!(#ty))
+}
@@ -1,7 +1,149 @@
 //! # About Rust
 //!
 //! `about-rust` is a project with examples about the Rust language.
+//!
+//! Without arguments, increments a number read from stdin.
+//! `types` prints the runtime type registry, `layout [name]` the memory
+//! layout of the registered types whose name contains `name`, and of the
+//! types shared with the C library. `hash [--json]` compares the quality and
+//! speed of hashers.
+
+use lib::describe::Describe;
+use lib::error::Error;
+use lib::exit::{self, code, Exit};
+use lib::layout::Layout;
+use local_crate::expr;
+use std::io;
+use std::num::TryFromIntError;
+use std::ops::Range;
+
+#[derive(Debug, Error, Describe)]
+#[error("unknown command `{0}`, expected `types`, `layout` or `hash`")]
+struct UsageError(String);
+
+/// The command given by the arguments.
+#[derive(Debug, Clone, PartialEq, Eq, Describe)]
+enum Command {
+    Increment,
+    Types,
+    Layout { name: String },
+    Hash { json: bool },
+}
+
+impl Command {
+    fn parse(arguments: &[&str]) -> Result<Command, UsageError> {
+        match arguments {
+            [] => Ok(Command::Increment),
+            ["types"] => Ok(Command::Types),
+            ["layout"] => Ok(Command::Layout {
+                name: String::new(),
+            }),
+            ["layout", name] => Ok(Command::Layout {
+                name: name.to_string(),
+            }),
+            ["hash"] => Ok(Command::Hash { json: false }),
+            ["hash", "--json"] => Ok(Command::Hash { json: true }),
+            _ => Err(UsageError(arguments.join(" "))),
+        }
+    }
+}
+
+fn main() -> Exit<(), Box<dyn std::error::Error>> {
+    exit::register::<io::Error>(code::IOERR);
+    exit::register::<expr::Error<Range<usize>>>(code::DATAERR);
+    exit::register::<TryFromIntError>(code::DATAERR);
+    exit::register::<UsageError>(code::USAGE);
+
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+    let arguments: Vec<&str> = arguments.iter().map(String::as_str).collect();
+    let result = match Command::parse(&arguments) {
+        Ok(Command::Increment) => lib::run(),
+        Ok(Command::Types) => {
+            types();
+            Ok(())
+        }
+        Ok(Command::Layout { name }) => {
+            layout(&name);
+            Ok(())
+        }
+        Ok(Command::Hash { json }) => {
+            hash(json);
+            Ok(())
+        }
+        Err(error) => Err(error.into()),
+    };
+    Exit(result)
+}
+
+/// The types of the binary, the ones of `lib` being registered by their
+/// users.
+fn register_types() {
+    use lib::register_type;
+
+    register_type!(Command);
+    register_type!(UsageError);
+}
+
+fn types() {
+    register_types();
+    for info in lib::registry::types() {
+        println!("{info}\n");
+    }
+}
+
+fn layout(name: &str) {
+    register_types();
+    for info in lib::registry::types().filter(|info| info.name().contains(name)) {
+        println!("{}\n", Layout::from_description(&info.description));
+    }
+
+    for (rust, c) in lib::clib::mirrored_layouts() {
+        if !rust.name.contains(name) && !c.name.contains(name) {
+            continue;
+        }
+        println!("{rust}\n\nC {c}");
+        let mismatches = rust.compare(&c);
+        if mismatches.is_empty() {
+            println!("matches the C layout");
+        }
+        for mismatch in mismatches {
+            println!("MISMATCH: {mismatch}");
+        }
+        println!();
+    }
+}
+
+fn hash(json: bool) {
+    use lib::hash::analysis::{self, Options};
+    use lib::hash::*;
+    use lib::type_map::BuildTypeIdHasher;
+    use std::hash::RandomState;
 
-fn main() {
-    lib::run();
+    let options = Options::default();
+    let analyses = [
+        analysis::analyze("std RandomState", &RandomState::new(), &options),
+        analysis::analyze(
+            "FNV-1a 32",
+            &BuildFnv1a32::default(),
+            &Options {
+                bits: 32,
+                ..options.clone()
+            },
+        ),
+        analysis::analyze("FNV-1a 64", &BuildFnv1a64::default(), &options),
+        analysis::analyze("SipHash-1-3", &BuildSipHasher13::new(1, 2), &options),
+        analysis::analyze("SipHash-2-4", &BuildSipHasher24::new(1, 2), &options),
+        analysis::analyze("xxHash64", &BuildXxHash64::default(), &options),
+        analysis::analyze("TypeIdHasher", &BuildTypeIdHasher::default(), &options),
+    ];
+    if json {
+        println!("{}", analysis::json(&analyses));
+    } else {
+        println!("{}", analysis::table(&analyses));
+        println!(
+            "\navalanche: probability of an output bit to flip with an input bit, ideally 0.500"
+        );
+        println!("bias: worst deviation of that probability, 0 to 1");
+        println!("key sets: chi-square z-score of the buckets, ideally within ±3, (collisions)");
+    }
 }
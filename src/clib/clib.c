@@ -1,6 +1,8 @@
 #include "clib.h"
 #include <stdarg.h>
+#include <stdatomic.h>
 #include <stdlib.h>
+#include <string.h>
 
 struct NLOpaqueType {
   NLValue value;
@@ -45,8 +47,156 @@ void NLOpaqueTypeTriggerCallback(NLOpaqueType const *instance) {
   }
 }
 
-void NLInitVector(int64_t *vector, int count) {
-  for (int i = 0; i < count; ++i) {
-    vector[i] = i;
+void NLInitVector(int64_t *vector, size_t count) {
+  NLFillRange(vector, count, 0, 1);
+}
+
+void NLFillRange(int64_t *vector, size_t count, int64_t start, int64_t step) {
+  uint64_t value = (uint64_t)start;
+  for (size_t i = 0; i < count; ++i) {
+    vector[i] = (int64_t)value;
+    value += (uint64_t)step;
+  }
+}
+
+void NLFillConstant(int64_t *vector, size_t count, int64_t value) {
+  for (size_t i = 0; i < count; ++i) {
+    vector[i] = value;
+  }
+}
+
+void NLFillGenerate(int64_t *vector, size_t count, void *context,
+                    NLGenerator generator) {
+  for (size_t i = 0; i < count; ++i) {
+    vector[i] = generator(context, i);
+  }
+}
+
+static atomic_size_t live_allocations = 0;
+
+// Returns NULL for empty buffers, which are never counted.
+static void *NLVariantAllocate(size_t size) {
+  if (size == 0) {
+    return NULL;
   }
-}
\ No newline at end of file
+  void *buffer = malloc(size);
+  if (!buffer) {
+    abort();
+  }
+  atomic_fetch_add(&live_allocations, 1);
+  return buffer;
+}
+
+static void NLVariantDeallocate(void *buffer) {
+  if (buffer) {
+    free(buffer);
+    atomic_fetch_sub(&live_allocations, 1);
+  }
+}
+
+NLVariant NLVariantCreateInt32(int32_t value) {
+  NLVariant variant = {.kind = NLVariantKindInt32};
+  variant.as.integer = value;
+  return variant;
+}
+
+NLVariant NLVariantCreateBool(bool value) {
+  NLVariant variant = {.kind = NLVariantKindBool};
+  variant.as.boolean = value;
+  return variant;
+}
+
+NLVariant NLVariantCreateDouble(double value) {
+  NLVariant variant = {.kind = NLVariantKindDouble};
+  variant.as.real = value;
+  return variant;
+}
+
+NLVariant NLVariantCreateString(char const *data, size_t length) {
+  NLVariant variant = {.kind = NLVariantKindString};
+  char *copy = (char *)NLVariantAllocate(length + 1);
+  memcpy(copy, data, length);
+  copy[length] = '\0';
+  variant.as.string.data = copy;
+  variant.as.string.length = length;
+  return variant;
+}
+
+NLVariant NLVariantCreateInt64Array(int64_t const *data, size_t count) {
+  NLVariant variant = {.kind = NLVariantKindInt64Array};
+  int64_t *copy = (int64_t *)NLVariantAllocate(count * sizeof(int64_t));
+  if (copy) {
+    memcpy(copy, data, count * sizeof(int64_t));
+  }
+  variant.as.array.data = copy;
+  variant.as.array.count = count;
+  return variant;
+}
+
+NLVariant NLVariantCreateSequence(size_t count) {
+  NLVariant variant = {.kind = NLVariantKindInt64Array};
+  int64_t *data = (int64_t *)NLVariantAllocate(count * sizeof(int64_t));
+  NLInitVector(data, count);
+  variant.as.array.data = data;
+  variant.as.array.count = count;
+  return variant;
+}
+
+void NLVariantDestroy(NLVariant *variant) {
+  switch (variant->kind) {
+  case NLVariantKindString:
+    NLVariantDeallocate(variant->as.string.data);
+    variant->as.string.data = NULL;
+    variant->as.string.length = 0;
+    break;
+  case NLVariantKindInt64Array:
+    NLVariantDeallocate(variant->as.array.data);
+    variant->as.array.data = NULL;
+    variant->as.array.count = 0;
+    break;
+  default:
+    break;
+  }
+}
+
+size_t NLVariantLiveAllocations(void) { return atomic_load(&live_allocations); }
+
+#define NL_FIELD(type, field, field_type)                                      \
+  { #field, #field_type, offsetof(type, field), sizeof(((type *)0)->field) }
+
+#define NL_TYPE(type, fields)                                                  \
+  { #type, sizeof(type), _Alignof(type), sizeof(fields) / sizeof(fields[0]),   \
+    fields }
+
+static NLFieldLayout const value_fields[] = {
+    NL_FIELD(NLValue, integer, int32_t),
+    NL_FIELD(NLValue, boolean, bool),
+};
+
+static NLFieldLayout const string_fields[] = {
+    NL_FIELD(NLString, data, char *),
+    NL_FIELD(NLString, length, size_t),
+};
+
+static NLFieldLayout const array_fields[] = {
+    NL_FIELD(NLInt64Array, data, int64_t *),
+    NL_FIELD(NLInt64Array, count, size_t),
+};
+
+static NLFieldLayout const variant_fields[] = {
+    NL_FIELD(NLVariant, kind, NLVariantKind),
+    NL_FIELD(NLVariant, as, union),
+};
+
+static NLTypeLayout const type_layouts[] = {
+    NL_TYPE(NLValue, value_fields),
+    {"NLVariantKind", sizeof(NLVariantKind), _Alignof(NLVariantKind), 0, NULL},
+    NL_TYPE(NLString, string_fields),
+    NL_TYPE(NLInt64Array, array_fields),
+    NL_TYPE(NLVariant, variant_fields),
+};
+
+NLTypeLayout const *NLTypeLayouts(size_t *count) {
+  *count = sizeof(type_layouts) / sizeof(type_layouts[0]);
+  return type_layouts;
+}
@@ -2,6 +2,7 @@
 #define clib_h
 
 #include <stdbool.h>
+#include <stddef.h>
 #include <stdint.h>
 
 typedef struct NLOpaqueType NLOpaqueType;
@@ -28,6 +29,105 @@ void NLOpaqueTypeRegisterCallback(NLOpaqueType *instance,
 
 void NLOpaqueTypeTriggerCallback(NLOpaqueType const *instance);
 
-void NLInitVector(int64_t *p, int count);
+void NLInitVector(int64_t *p, size_t count);
+
+// Fill strategies, `vector` must have room for `count` elements.
+
+typedef int64_t (*NLGenerator)(void *, size_t);
+
+// vector[i] = start + i * step, wrapping on overflow.
+void NLFillRange(int64_t *vector, size_t count, int64_t start, int64_t step);
+
+void NLFillConstant(int64_t *vector, size_t count, int64_t value);
+
+// vector[i] = generator(context, i), called in increasing index order.
+void NLFillGenerate(int64_t *vector, size_t count, void *context,
+                    NLGenerator generator);
+
+// Tagged value
+
+typedef enum
+{
+    NLVariantKindInt32,
+    NLVariantKindBool,
+    NLVariantKindDouble,
+    NLVariantKindString,
+    NLVariantKindInt64Array,
+} NLVariantKind;
+
+// UTF-8 bytes, not required to be NUL-terminated by callers.
+// `data` is always NUL-terminated when owned by a NLVariant.
+typedef struct
+{
+    char *data;
+    size_t length;
+} NLString;
+
+typedef struct
+{
+    int64_t *data;
+    size_t count;
+} NLInt64Array;
+
+/*
+    Ownership:
+    - A variant of kind String or Int64Array owns its buffer.
+    - The Create functions copy their input, the caller keeps ownership of it.
+    - Every variant must be released exactly once with NLVariantDestroy.
+    - The `as.string.data` and `as.array.data` buffers are borrowed from the
+      variant and are valid until it is destroyed.
+*/
+typedef struct
+{
+    NLVariantKind kind;
+    union
+    {
+        int32_t integer;
+        bool boolean;
+        double real;
+        NLString string;
+        NLInt64Array array;
+    } as;
+} NLVariant;
+
+NLVariant NLVariantCreateInt32(int32_t value);
+
+NLVariant NLVariantCreateBool(bool value);
+
+NLVariant NLVariantCreateDouble(double value);
+
+NLVariant NLVariantCreateString(char const *data, size_t length);
+
+NLVariant NLVariantCreateInt64Array(int64_t const *data, size_t count);
+
+// An Int64Array of `count` elements filled by NLInitVector.
+NLVariant NLVariantCreateSequence(size_t count);
+
+void NLVariantDestroy(NLVariant *variant);
+
+// Number of buffers allocated by the Create functions and not yet destroyed.
+size_t NLVariantLiveAllocations(void);
+
+// Layouts of the types shared with Rust, as compiled
+
+typedef struct
+{
+    char const *name;
+    char const *type;
+    size_t offset;
+    size_t size;
+} NLFieldLayout;
+
+typedef struct
+{
+    char const *name;
+    size_t size;
+    size_t align;
+    size_t field_count;
+    NLFieldLayout const *fields;
+} NLTypeLayout;
+
+// A static array of `*count` layouts.
+NLTypeLayout const *NLTypeLayouts(size_t *count);
 
 #endif
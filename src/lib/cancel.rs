@@ -0,0 +1,346 @@
+//! Cancellation tokens, to stop threads blocked in a sleep, a `Condvar` wait
+//! or a channel receive.
+//!
+//! A token is cancelled by [`cancel`](CancellationToken::cancel) on any of
+//! its clones or of its ancestors, or when its deadline passes. Deadlines are
+//! not timers: callbacks registered by
+//! [`on_cancel`](CancellationToken::on_cancel) only run on `cancel`, the
+//! blocking functions waiting at most until the deadline.
+//!
+//! # Examples
+//! ```
+//! use lib::cancel::{CancelError, CancellationToken};
+//! use std::thread;
+//! use std::time::Duration;
+//!
+//! let token = CancellationToken::new();
+//! let workers: Vec<_> = (0..4)
+//!     .map(|_| {
+//!         let token = token.child();
+//!         thread::spawn(move || {
+//!             let mut ticks = 0;
+//!             while token.sleep(Duration::from_millis(1)).is_ok() {
+//!                 ticks += 1;
+//!             }
+//!             ticks
+//!         })
+//!     })
+//!     .collect();
+//! thread::sleep(Duration::from_millis(20));
+//! token.cancel();
+//! for worker in workers {
+//!     assert!(worker.join().unwrap() > 0);
+//! }
+//!
+//! let token = CancellationToken::with_timeout(Duration::from_millis(10));
+//! assert_eq!(token.wait(), CancelError::DeadlineExceeded);
+//! ```
+
+use crate::error::Error;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+
+type Callback = Box<dyn FnOnce() + Send>;
+
+/// The longest wait of [`CancellationToken::wait_while`] between checks of
+/// the token, bounding the delay of a missed cancellation.
+const WAIT_SLICE: Duration = Duration::from_millis(10);
+
+/// A cancellation flag shared by its clones, and propagated to its children.
+#[derive(Clone)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    cancelled: AtomicBool,
+    /// The earliest of its own deadline and the ones of its ancestors
+    deadline: Option<Instant>,
+    state: Mutex<State>,
+    /// Notified on cancel, for `wait` and `sleep`
+    condvar: Condvar,
+    next_callback: AtomicU64,
+}
+
+#[derive(Default)]
+struct State {
+    children: Vec<Weak<Inner>>,
+    callbacks: Vec<(u64, Callback)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum CancelError {
+    #[error("the operation was cancelled")]
+    Cancelled,
+    #[error("the deadline of the operation passed")]
+    DeadlineExceeded,
+}
+
+impl Inner {
+    fn new(deadline: Option<Instant>, cancelled: bool) -> Arc<Inner> {
+        Arc::new(Inner {
+            cancelled: AtomicBool::new(cancelled),
+            deadline,
+            state: Mutex::new(State::default()),
+            condvar: Condvar::new(),
+            next_callback: AtomicU64::new(0),
+        })
+    }
+
+    // Callbacks run without the lock, so a poisoned state is still
+    // consistent
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    fn cancel(&self) {
+        let mut state = self.lock();
+        if self.cancelled.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let children = std::mem::take(&mut state.children);
+        let callbacks = std::mem::take(&mut state.callbacks);
+        drop(state);
+        self.condvar.notify_all();
+        for (_, callback) in callbacks {
+            callback();
+        }
+        for child in children.iter().filter_map(Weak::upgrade) {
+            child.cancel();
+        }
+    }
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken {
+            inner: Inner::new(None, false),
+        }
+    }
+
+    /// A token cancelled at `deadline`.
+    pub fn with_deadline(deadline: Instant) -> CancellationToken {
+        CancellationToken {
+            inner: Inner::new(Some(deadline), false),
+        }
+    }
+
+    /// A token cancelled `timeout` from now, without deadline if too far to
+    /// be represented.
+    pub fn with_timeout(timeout: Duration) -> CancellationToken {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => CancellationToken::with_deadline(deadline),
+            None => CancellationToken::new(),
+        }
+    }
+
+    /// A token cancelled with this one, but whose cancellation doesn't
+    /// cancel this one.
+    pub fn child(&self) -> CancellationToken {
+        self.child_with(self.inner.deadline)
+    }
+
+    /// A child cancelled at `deadline` at the latest.
+    pub fn child_with_deadline(&self, deadline: Instant) -> CancellationToken {
+        let deadline = self
+            .inner
+            .deadline
+            .map_or(deadline, |own| own.min(deadline));
+        self.child_with(Some(deadline))
+    }
+
+    /// A child cancelled `timeout` from now at the latest, or as
+    /// [`child`](CancellationToken::child) if too far to be represented.
+    pub fn child_with_timeout(&self, timeout: Duration) -> CancellationToken {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.child_with_deadline(deadline),
+            None => self.child(),
+        }
+    }
+
+    fn child_with(&self, deadline: Option<Instant>) -> CancellationToken {
+        // Under the lock, not to miss a cancellation in between
+        let mut state = self.inner.lock();
+        let child = Inner::new(deadline, self.inner.cancelled.load(Ordering::SeqCst));
+        state.children.retain(|child| child.strong_count() > 0);
+        state.children.push(Arc::downgrade(&child));
+        CancellationToken { inner: child }
+    }
+
+    /// Cancels the token, its clones and its descendants, waking up the
+    /// threads waiting on them.
+    pub fn cancel(&self) {
+        self.inner.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.check().is_err()
+    }
+
+    /// Why the token is cancelled, if it is.
+    pub fn check(&self) -> Result<(), CancelError> {
+        if self.inner.cancelled.load(Ordering::SeqCst) {
+            Err(CancelError::Cancelled)
+        } else if self.remaining() == Some(Duration::ZERO) {
+            Err(CancelError::DeadlineExceeded)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.inner.deadline
+    }
+
+    /// The time left before the deadline, `None` without deadline.
+    pub fn remaining(&self) -> Option<Duration> {
+        let deadline = self.inner.deadline?;
+        Some(deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Runs `callback` on cancel, or now if the token is already cancelled,
+    /// unless the registration is dropped before.
+    ///
+    /// The callback runs on the thread calling `cancel`, and must not wait
+    /// for a lock this thread may hold.
+    pub fn on_cancel(&self, callback: impl FnOnce() + Send + 'static) -> Registration {
+        let id = self.inner.next_callback.fetch_add(1, Ordering::Relaxed);
+        let mut state = self.inner.lock();
+        if self.inner.cancelled.load(Ordering::SeqCst) {
+            drop(state);
+            callback();
+        } else {
+            state.callbacks.push((id, Box::new(callback)));
+        }
+        Registration {
+            token: Arc::downgrade(&self.inner),
+            id,
+        }
+    }
+
+    /// Waits for the token to be cancelled.
+    pub fn wait(&self) -> CancelError {
+        match self.sleep_until(None) {
+            Err(error) => error,
+            Ok(()) => unreachable!("waiting without deadline"),
+        }
+    }
+
+    /// Sleeps for `duration`, failing as soon as the token is cancelled. A
+    /// duration too far to be represented, as `Duration::MAX`, waits for the
+    /// cancellation.
+    pub fn sleep(&self, duration: Duration) -> Result<(), CancelError> {
+        self.sleep_until(Instant::now().checked_add(duration))
+    }
+
+    fn sleep_until(&self, until: Option<Instant>) -> Result<(), CancelError> {
+        let mut state = self.inner.lock();
+        loop {
+            self.check()?;
+            let timeout = match (until, self.remaining()) {
+                (Some(until), remaining) => {
+                    let timeout = until.saturating_duration_since(Instant::now());
+                    if timeout.is_zero() {
+                        return Ok(());
+                    }
+                    Some(remaining.map_or(timeout, |remaining| remaining.min(timeout)))
+                }
+                (None, remaining) => remaining,
+            };
+            state = match timeout {
+                None => self
+                    .inner
+                    .condvar
+                    .wait(state)
+                    .unwrap_or_else(|error| error.into_inner()),
+                Some(timeout) => {
+                    self.inner
+                        .condvar
+                        .wait_timeout(state, timeout)
+                        .unwrap_or_else(|error| error.into_inner())
+                        .0
+                }
+            };
+        }
+    }
+
+    /// As `Condvar::wait_while`, waiting while `condition` is true, but
+    /// failing as soon as the token is cancelled.
+    ///
+    /// The condition variable must be notified when the value changes, as
+    /// for `Condvar::wait_while`. The token may be cancelled while holding
+    /// the mutex, the waiting thread then seeing it within a few
+    /// milliseconds.
+    ///
+    /// # Examples
+    /// ```
+    /// use lib::cancel::{CancelError, CancellationToken};
+    /// use std::sync::{Arc, Condvar, Mutex};
+    /// use std::time::Duration;
+    ///
+    /// let pair = Arc::new((Mutex::new(false), Condvar::new()));
+    /// let token = CancellationToken::with_timeout(Duration::from_millis(10));
+    /// let started = token.wait_while(&pair, |started| !*started);
+    /// assert_eq!(started.err(), Some(CancelError::DeadlineExceeded));
+    /// ```
+    pub fn wait_while<'a, T: Send + 'static>(
+        &self,
+        pair: &'a Arc<(Mutex<T>, Condvar)>,
+        mut condition: impl FnMut(&mut T) -> bool,
+    ) -> Result<MutexGuard<'a, T>, CancelError> {
+        let notified = pair.clone();
+        let _registration = self.on_cancel(move || {
+            let (lock, condvar) = &*notified;
+            // Not waiting for the lock, which the cancelling thread may hold.
+            // A notification sent between the check of the waiting thread
+            // and its wait is lost, and it checks again after a slice.
+            drop(lock.try_lock());
+            condvar.notify_all();
+        });
+        let (lock, condvar) = &**pair;
+        let mut guard = lock.lock().unwrap_or_else(|error| error.into_inner());
+        while condition(&mut guard) {
+            self.check()?;
+            let timeout = self
+                .remaining()
+                .map_or(WAIT_SLICE, |remaining| remaining.min(WAIT_SLICE));
+            guard = condvar
+                .wait_timeout(guard, timeout)
+                .unwrap_or_else(|error| error.into_inner())
+                .0;
+        }
+        Ok(guard)
+    }
+}
+
+impl Default for CancellationToken {
+    fn default() -> CancellationToken {
+        CancellationToken::new()
+    }
+}
+
+impl std::fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.inner.cancelled.load(Ordering::SeqCst))
+            .field("deadline", &self.inner.deadline)
+            .finish()
+    }
+}
+
+/// A callback of [`on_cancel`](CancellationToken::on_cancel), unregistered
+/// when dropped.
+#[must_use = "the callback is unregistered when the registration is dropped"]
+pub struct Registration {
+    token: Weak<Inner>,
+    id: u64,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Some(token) = self.token.upgrade() {
+            token.lock().callbacks.retain(|(id, _)| *id != self.id);
+        }
+    }
+}
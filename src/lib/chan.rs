@@ -0,0 +1,626 @@
+//! A bounded channel with several producers and several consumers, and
+//! [`select!`](crate::select!) to receive from the first ready of several
+//! channels.
+//!
+//! Senders block while the channel is full, receivers while it is empty,
+//! waiting on a `Condvar`. As with `std::sync::mpsc`, sending fails once
+//! all the receivers are dropped, and receiving fails once all the senders
+//! are dropped and the messages left are received.
+//!
+//! # Examples
+//! ```
+//! use lib::chan;
+//! use std::thread;
+//!
+//! let (sender, receiver) = chan::bounded(2);
+//! let consumers: Vec<_> = (0..2)
+//!     .map(|_| {
+//!         let receiver = receiver.clone();
+//!         thread::spawn(move || receiver.iter().sum::<i32>())
+//!     })
+//!     .collect();
+//! for x in 1..=100 {
+//!     sender.send(x).unwrap();
+//! }
+//! drop(sender);
+//! let total: i32 = consumers.into_iter().map(|consumer| consumer.join().unwrap()).sum();
+//! assert_eq!(total, 5050);
+//! ```
+
+use crate::cancel::{CancelError, CancellationToken};
+use crate::error::Error as DeriveError;
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+pub use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError};
+
+/// A channel holding at most `capacity` messages.
+///
+/// # Panics
+/// If `capacity` is 0, rendezvous channels not being supported.
+pub fn bounded<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "the capacity of a channel must be positive");
+    let channel = Arc::new(Channel {
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(capacity),
+            capacity,
+            senders: 1,
+            receivers: 1,
+            selectors: Vec::new(),
+        }),
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+    });
+    (
+        Sender {
+            channel: channel.clone(),
+        },
+        Receiver { channel },
+    )
+}
+
+struct Channel<T> {
+    state: Mutex<State<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    capacity: usize,
+    senders: usize,
+    receivers: usize,
+    /// The `select` calls waiting on this channel
+    selectors: Vec<Arc<Signal>>,
+}
+
+impl<T> Channel<T> {
+    // Messages are moved in and out of the queue without panicking while
+    // the lock is held, so a poisoned state is still consistent
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+impl<T> State<T> {
+    fn notify_selectors(&self) {
+        for signal in &self.selectors {
+            signal.notify();
+        }
+    }
+}
+
+/// The time left before `deadline`, `None` once passed.
+fn remaining(deadline: Instant) -> Option<Duration> {
+    deadline.checked_duration_since(Instant::now())
+}
+
+// ____________________________________________________________
+// Sender
+
+pub struct Sender<T> {
+    channel: Arc<Channel<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Waits for room in the channel, failing if all the receivers are
+    /// dropped.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let mut state = self.channel.lock();
+        loop {
+            if state.receivers == 0 {
+                return Err(SendError(value));
+            }
+            if state.queue.len() < state.capacity {
+                self.push(state, value);
+                return Ok(());
+            }
+            state = self
+                .channel
+                .not_full
+                .wait(state)
+                .unwrap_or_else(|error| error.into_inner());
+        }
+    }
+
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let state = self.channel.lock();
+        if state.receivers == 0 {
+            Err(TrySendError::Disconnected(value))
+        } else if state.queue.len() == state.capacity {
+            Err(TrySendError::Full(value))
+        } else {
+            self.push(state, value);
+            Ok(())
+        }
+    }
+
+    pub fn send_timeout(&self, value: T, timeout: Duration) -> Result<(), SendTimeoutError<T>> {
+        let Some(deadline) = Instant::now().checked_add(timeout) else {
+            // Too far to be represented, as never
+            return self
+                .send(value)
+                .map_err(|SendError(value)| SendTimeoutError::Disconnected(value));
+        };
+        let mut state = self.channel.lock();
+        loop {
+            if state.receivers == 0 {
+                return Err(SendTimeoutError::Disconnected(value));
+            }
+            if state.queue.len() < state.capacity {
+                self.push(state, value);
+                return Ok(());
+            }
+            let Some(timeout) = remaining(deadline) else {
+                return Err(SendTimeoutError::Timeout(value));
+            };
+            state = self
+                .channel
+                .not_full
+                .wait_timeout(state, timeout)
+                .unwrap_or_else(|error| error.into_inner())
+                .0;
+        }
+    }
+
+    fn push(&self, mut state: MutexGuard<'_, State<T>>, value: T) {
+        state.queue.push_back(value);
+        state.notify_selectors();
+        drop(state);
+        self.channel.not_empty.notify_one();
+    }
+
+    pub fn len(&self) -> usize {
+        self.channel.lock().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channel.lock().queue.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.channel.lock().capacity
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        self.channel.lock().senders += 1;
+        Sender {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.channel.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            state.notify_selectors();
+            drop(state);
+            self.channel.not_empty.notify_all();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender").finish_non_exhaustive()
+    }
+}
+
+/// As `std::sync::mpsc::SendTimeoutError`, not stable yet.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SendTimeoutError<T> {
+    Timeout(T),
+    Disconnected(T),
+}
+
+impl<T> SendTimeoutError<T> {
+    pub fn into_inner(self) -> T {
+        match self {
+            SendTimeoutError::Timeout(value) | SendTimeoutError::Disconnected(value) => value,
+        }
+    }
+}
+
+// Without the message, which may not implement `Debug`
+impl<T> fmt::Debug for SendTimeoutError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendTimeoutError::Timeout(_) => f.write_str("Timeout(..)"),
+            SendTimeoutError::Disconnected(_) => f.write_str("Disconnected(..)"),
+        }
+    }
+}
+
+impl<T> fmt::Display for SendTimeoutError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendTimeoutError::Timeout(_) => f.write_str("timed out waiting on send operation"),
+            SendTimeoutError::Disconnected(_) => f.write_str("sending on a closed channel"),
+        }
+    }
+}
+
+impl<T> Error for SendTimeoutError<T> {}
+
+// ____________________________________________________________
+// Receiver
+
+/// A consumer of the channel, cloned for each consumer thread instead of
+/// being shared behind a mutex.
+pub struct Receiver<T> {
+    channel: Arc<Channel<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Waits for a message, failing once all the senders are dropped and the
+    /// channel is empty.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let mut state = self.channel.lock();
+        loop {
+            if let Some(value) = self.pop(&mut state) {
+                return Ok(value);
+            }
+            if state.senders == 0 {
+                return Err(RecvError);
+            }
+            state = self
+                .channel
+                .not_empty
+                .wait(state)
+                .unwrap_or_else(|error| error.into_inner());
+        }
+    }
+
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.channel.lock();
+        match self.pop(&mut state) {
+            Some(value) => Ok(value),
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let Some(deadline) = Instant::now().checked_add(timeout) else {
+            // Too far to be represented, as never
+            return self
+                .recv()
+                .map_err(|RecvError| RecvTimeoutError::Disconnected);
+        };
+        let mut state = self.channel.lock();
+        loop {
+            if let Some(value) = self.pop(&mut state) {
+                return Ok(value);
+            }
+            if state.senders == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let Some(timeout) = remaining(deadline) else {
+                return Err(RecvTimeoutError::Timeout);
+            };
+            state = self
+                .channel
+                .not_empty
+                .wait_timeout(state, timeout)
+                .unwrap_or_else(|error| error.into_inner())
+                .0;
+        }
+    }
+
+    /// Waits for a message, failing once all the senders are dropped and the
+    /// channel is empty, or as soon as `token` is cancelled, even with
+    /// messages left.
+    pub fn recv_cancellable(&self, token: &CancellationToken) -> Result<T, RecvCancelError>
+    where
+        T: Send + 'static,
+    {
+        let channel = self.channel.clone();
+        // Locking before notifying, the receiver either sees the
+        // cancellation or is already waiting
+        let _registration = token.on_cancel(move || {
+            drop(channel.lock());
+            channel.not_empty.notify_all();
+        });
+        let mut state = self.channel.lock();
+        loop {
+            token.check()?;
+            if let Some(value) = self.pop(&mut state) {
+                return Ok(value);
+            }
+            if state.senders == 0 {
+                return Err(RecvCancelError::Disconnected);
+            }
+            state = match token.remaining() {
+                None => self
+                    .channel
+                    .not_empty
+                    .wait(state)
+                    .unwrap_or_else(|error| error.into_inner()),
+                Some(timeout) => {
+                    self.channel
+                        .not_empty
+                        .wait_timeout(state, timeout)
+                        .unwrap_or_else(|error| error.into_inner())
+                        .0
+                }
+            };
+        }
+    }
+
+    fn pop(&self, state: &mut State<T>) -> Option<T> {
+        let value = state.queue.pop_front()?;
+        self.channel.not_full.notify_one();
+        Some(value)
+    }
+
+    /// The messages until all the senders are dropped.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(|| self.recv().ok())
+    }
+
+    /// The messages already in the channel.
+    pub fn try_iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(|| self.try_recv().ok())
+    }
+
+    pub fn len(&self) -> usize {
+        self.channel.lock().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channel.lock().queue.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.channel.lock().capacity
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Receiver<T> {
+        self.channel.lock().receivers += 1;
+        Receiver {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.channel.lock();
+        state.receivers -= 1;
+        if state.receivers == 0 {
+            drop(state);
+            self.channel.not_full.notify_all();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver").finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DeriveError)]
+pub enum RecvCancelError {
+    #[error("receiving on a closed channel")]
+    Disconnected,
+    #[error(transparent)]
+    Cancelled(#[from] CancelError),
+}
+
+// ____________________________________________________________
+// Select
+
+/// Wakes up a `select` when one of its channels receives a message or is
+/// disconnected.
+#[doc(hidden)]
+#[derive(Default)]
+pub struct Signal {
+    ready: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Signal {
+    fn notify(&self) {
+        *self.ready.lock().unwrap_or_else(|error| error.into_inner()) = true;
+        self.condvar.notify_one();
+    }
+
+    /// Returns false on timeout.
+    fn wait(&self, deadline: Option<Instant>) -> bool {
+        let mut ready = self.ready.lock().unwrap_or_else(|error| error.into_inner());
+        while !*ready {
+            ready = match deadline {
+                None => self
+                    .condvar
+                    .wait(ready)
+                    .unwrap_or_else(|error| error.into_inner()),
+                Some(deadline) => {
+                    let Some(timeout) = remaining(deadline) else {
+                        return false;
+                    };
+                    self.condvar
+                        .wait_timeout(ready, timeout)
+                        .unwrap_or_else(|error| error.into_inner())
+                        .0
+                }
+            };
+        }
+        *ready = false;
+        true
+    }
+}
+
+/// The receivers [`select!`](crate::select!) can wait on, whatever the type
+/// of their messages.
+pub trait Selectable {
+    /// A `Box<Result<T, RecvError>>` if a message is received or the channel
+    /// is disconnected.
+    #[doc(hidden)]
+    fn try_select(&self) -> Option<Box<dyn Any>>;
+    #[doc(hidden)]
+    fn register(&self, signal: &Arc<Signal>);
+    #[doc(hidden)]
+    fn unregister(&self, signal: &Arc<Signal>);
+}
+
+impl<T: 'static> Selectable for Receiver<T> {
+    fn try_select(&self) -> Option<Box<dyn Any>> {
+        let message: Result<T, RecvError> = match self.try_recv() {
+            Ok(value) => Ok(value),
+            Err(TryRecvError::Disconnected) => Err(RecvError),
+            Err(TryRecvError::Empty) => return None,
+        };
+        Some(Box::new(message))
+    }
+
+    fn register(&self, signal: &Arc<Signal>) {
+        self.channel.lock().selectors.push(signal.clone());
+    }
+
+    fn unregister(&self, signal: &Arc<Signal>) {
+        let mut state = self.channel.lock();
+        state.selectors.retain(|other| !Arc::ptr_eq(other, signal));
+    }
+}
+
+impl<S: Selectable + ?Sized> Selectable for &S {
+    fn try_select(&self) -> Option<Box<dyn Any>> {
+        (**self).try_select()
+    }
+
+    fn register(&self, signal: &Arc<Signal>) {
+        (**self).register(signal)
+    }
+
+    fn unregister(&self, signal: &Arc<Signal>) {
+        (**self).unregister(signal)
+    }
+}
+
+thread_local! {
+    /// The receiver checked first by the next `select` of the thread
+    static SELECT_START: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Receives from the first of `receivers` with a message or disconnected,
+/// returning its index and a `Box<Result<T, RecvError>>`, or `None` on
+/// timeout. [`select!`](crate::select!) is easier to use.
+///
+/// Each call of a thread checks the receivers from the one after where the
+/// previous call started, so that a receiver always ready, as a
+/// disconnected one, doesn't starve the others.
+pub fn select(
+    receivers: &[&dyn Selectable],
+    timeout: Option<Duration>,
+) -> Option<(usize, Box<dyn Any>)> {
+    // A timeout too far to be represented is no timeout
+    let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+    let signal = Arc::new(Signal::default());
+    // Registered before looking at the channels, not to miss a message
+    // sent in between
+    for receiver in receivers {
+        receiver.register(&signal);
+    }
+    let start = SELECT_START.get();
+    SELECT_START.set(start.wrapping_add(1));
+    let selected = loop {
+        let selected = (0..receivers.len())
+            .map(|turn| (start + turn) % receivers.len())
+            .find_map(|index| Some((index, receivers[index].try_select()?)));
+        if selected.is_some() || !signal.wait(deadline) {
+            break selected;
+        }
+    };
+    for receiver in receivers {
+        receiver.unregister(&signal);
+    }
+    selected
+}
+
+/// The message of the receiver selected by [`select`], typed as the
+/// receiver.
+#[doc(hidden)]
+pub fn __received<T: 'static>(_: &Receiver<T>, message: Box<dyn Any>) -> Result<T, RecvError> {
+    *message.downcast().unwrap()
+}
+
+/// Waits for a message on several receivers, and runs the arm of the first
+/// ready one, with `Ok(message)`, or `Err(RecvError)` if it is disconnected.
+///
+/// The receivers are checked in turn from a different one at each call, as
+/// by [`select`], and are expressions evaluated twice, usually variables.
+/// With a `default(timeout)` arm, waits at most `timeout`.
+///
+/// # Examples
+/// ```
+/// use lib::chan;
+/// use std::time::Duration;
+///
+/// let (numbers, number_receiver) = chan::bounded::<i32>(1);
+/// let (words, word_receiver) = chan::bounded(1);
+/// words.send("hello").unwrap();
+///
+/// let received = lib::select! {
+///     recv(number_receiver) -> number => number.unwrap().to_string(),
+///     recv(word_receiver) -> word => word.unwrap().to_uppercase(),
+/// };
+/// assert_eq!(received, "HELLO");
+///
+/// drop(words);
+/// lib::select! {
+///     recv(number_receiver) -> number => panic!("no number was sent"),
+///     recv(word_receiver) -> word => assert!(word.is_err()),
+///     default(Duration::from_millis(10)) => unreachable!(),
+/// }
+/// ```
+#[macro_export]
+macro_rules! select {
+    (
+        $(recv($receiver:expr) -> $message:pat => $body:expr,)+
+        default($timeout:expr) => $default:expr $(,)?
+    ) => {{
+        let __receivers: &[&dyn $crate::chan::Selectable] = &[$(&$receiver),+];
+        match $crate::chan::select(__receivers, ::std::option::Option::Some($timeout)) {
+            ::std::option::Option::Some(__selected) => {
+                $crate::__select_arms!(__selected, 0usize; $($receiver, $message, $body;)+)
+            }
+            ::std::option::Option::None => $default,
+        }
+    }};
+    ($(recv($receiver:expr) -> $message:pat => $body:expr),+ $(,)?) => {{
+        let __receivers: &[&dyn $crate::chan::Selectable] = &[$(&$receiver),+];
+        // Without timeout, `select` returns a message
+        let __selected = $crate::chan::select(__receivers, ::std::option::Option::None).unwrap();
+        $crate::__select_arms!(__selected, 0usize; $($receiver, $message, $body;)+)
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __select_arms {
+    ($selected:ident, $index:expr; $receiver:expr, $message:pat, $body:expr; $($rest:tt)*) => {
+        if $selected.0 == $index {
+            let $message = $crate::chan::__received(&$receiver, $selected.1);
+            $body
+        } else {
+            $crate::__select_arms!($selected, $index + 1; $($rest)*)
+        }
+    };
+    ($selected:ident, $index:expr;) => {
+        ::std::unreachable!()
+    };
+}
+
+pub use crate::select;
@@ -0,0 +1,522 @@
+//! Safe bindings to the C library in `src/clib`.
+
+use crate::describe::Describe;
+use crate::layout::{FieldLayout, Layout};
+use std::any::Any;
+use std::error::Error;
+use std::ffi::{c_char, c_void, CStr};
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::panic::{self, AssertUnwindSafe};
+use std::{ptr, slice, str};
+
+/// The tag of a [`Value`], mirrors `NLVariantKind`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Describe)]
+pub enum ValueKind {
+    Int32,
+    Bool,
+    Double,
+    String,
+    Int64Array,
+}
+
+mod ffi {
+    use super::ValueKind;
+    use crate::describe::Describe;
+    use std::ffi::{c_char, c_void};
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Describe)]
+    pub struct NLString {
+        pub data: *mut c_char,
+        pub length: usize,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Describe)]
+    pub struct NLInt64Array {
+        pub data: *mut i64,
+        pub count: usize,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union NLVariantData {
+        pub integer: i32,
+        pub boolean: bool,
+        pub real: f64,
+        pub string: NLString,
+        pub array: NLInt64Array,
+    }
+
+    #[repr(C)]
+    #[derive(Describe)]
+    pub struct NLVariant {
+        pub kind: ValueKind,
+        pub data: NLVariantData,
+    }
+
+    #[repr(C)]
+    pub struct NLFieldLayout {
+        pub name: *const c_char,
+        pub r#type: *const c_char,
+        pub offset: usize,
+        pub size: usize,
+    }
+
+    #[repr(C)]
+    pub struct NLTypeLayout {
+        pub name: *const c_char,
+        pub size: usize,
+        pub align: usize,
+        pub field_count: usize,
+        pub fields: *const NLFieldLayout,
+    }
+
+    #[link(name = "clib")]
+    extern "C" {
+        pub fn NLVariantCreateInt32(value: i32) -> NLVariant;
+        pub fn NLVariantCreateBool(value: bool) -> NLVariant;
+        pub fn NLVariantCreateDouble(value: f64) -> NLVariant;
+        pub fn NLVariantCreateString(data: *const c_char, length: usize) -> NLVariant;
+        pub fn NLVariantCreateInt64Array(data: *const i64, count: usize) -> NLVariant;
+        pub fn NLVariantCreateSequence(count: usize) -> NLVariant;
+        pub fn NLVariantDestroy(variant: *mut NLVariant);
+        pub fn NLVariantLiveAllocations() -> usize;
+        pub fn NLTypeLayouts(count: *mut usize) -> *const NLTypeLayout;
+
+        pub fn NLFillRange(vector: *mut i64, count: usize, start: i64, step: i64);
+        pub fn NLFillConstant(vector: *mut i64, count: usize, value: i64);
+        pub fn NLFillGenerate(
+            vector: *mut i64,
+            count: usize,
+            context: *mut c_void,
+            generator: extern "C" fn(*mut c_void, usize) -> i64,
+        );
+    }
+}
+
+/// An owned `NLVariant`, released with `NLVariantDestroy` on drop.
+///
+/// # Examples
+/// ```
+/// use lib::clib::Value;
+///
+/// let value = Value::from("hello");
+/// assert_eq!(String::try_from(&value).unwrap(), "hello");
+/// assert!(i32::try_from(&value).is_err());
+/// ```
+pub struct Value(ffi::NLVariant);
+
+// The buffers of a variant are owned by it and never shared.
+unsafe impl Send for Value {}
+unsafe impl Sync for Value {}
+
+impl Value {
+    /// An `Int64Array` holding `0..count`, filled by `NLInitVector`.
+    pub fn sequence(count: usize) -> Value {
+        Value(unsafe { ffi::NLVariantCreateSequence(count) })
+    }
+
+    pub fn kind(&self) -> ValueKind {
+        self.0.kind
+    }
+
+    pub fn as_i32(&self) -> Option<i32> {
+        match self.kind() {
+            ValueKind::Int32 => Some(unsafe { self.0.data.integer }),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.kind() {
+            ValueKind::Bool => Some(unsafe { self.0.data.boolean }),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.kind() {
+            ValueKind::Double => Some(unsafe { self.0.data.real }),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self.kind() {
+            ValueKind::String => unsafe {
+                let string = self.0.data.string;
+                let bytes = slice::from_raw_parts(string.data.cast::<u8>(), string.length);
+                // Strings are only created from a `&str`.
+                Some(str::from_utf8_unchecked(bytes))
+            },
+            _ => None,
+        }
+    }
+
+    pub fn as_slice(&self) -> Option<&[i64]> {
+        match self.kind() {
+            ValueKind::Int64Array => unsafe {
+                let array = self.0.data.array;
+                if array.data.is_null() {
+                    Some(&[])
+                } else {
+                    Some(slice::from_raw_parts(array.data, array.count))
+                }
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Number of buffers owned by values that are still alive.
+pub fn live_allocations() -> usize {
+    unsafe { ffi::NLVariantLiveAllocations() }
+}
+
+impl Drop for Value {
+    fn drop(&mut self) {
+        unsafe { ffi::NLVariantDestroy(&mut self.0) }
+    }
+}
+
+impl Clone for Value {
+    fn clone(&self) -> Value {
+        match self.kind() {
+            ValueKind::String => Value::from(self.as_str().unwrap()),
+            ValueKind::Int64Array => Value::from(self.as_slice().unwrap()),
+            _ => Value(ffi::NLVariant {
+                kind: self.0.kind,
+                data: self.0.data,
+            }),
+        }
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut tuple = f.debug_tuple(&format!("{:?}", self.kind()));
+        match self.kind() {
+            ValueKind::Int32 => tuple.field(&self.as_i32().unwrap()),
+            ValueKind::Bool => tuple.field(&self.as_bool().unwrap()),
+            ValueKind::Double => tuple.field(&self.as_f64().unwrap()),
+            ValueKind::String => tuple.field(&self.as_str().unwrap()),
+            ValueKind::Int64Array => tuple.field(&self.as_slice().unwrap()),
+        };
+        tuple.finish()
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        self.kind() == other.kind()
+            && match self.kind() {
+                ValueKind::Int32 => self.as_i32() == other.as_i32(),
+                ValueKind::Bool => self.as_bool() == other.as_bool(),
+                ValueKind::Double => self.as_f64() == other.as_f64(),
+                ValueKind::String => self.as_str() == other.as_str(),
+                ValueKind::Int64Array => self.as_slice() == other.as_slice(),
+            }
+    }
+}
+
+// ____________________________________________________________
+// Native types to Value
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Value {
+        Value(unsafe { ffi::NLVariantCreateInt32(value) })
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Value {
+        Value(unsafe { ffi::NLVariantCreateBool(value) })
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Value {
+        Value(unsafe { ffi::NLVariantCreateDouble(value) })
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Value {
+        Value(unsafe { ffi::NLVariantCreateString(value.as_ptr().cast(), value.len()) })
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Value {
+        Value::from(value.as_str())
+    }
+}
+
+impl From<&[i64]> for Value {
+    fn from(value: &[i64]) -> Value {
+        let data = if value.is_empty() {
+            ptr::null()
+        } else {
+            value.as_ptr()
+        };
+        Value(unsafe { ffi::NLVariantCreateInt64Array(data, value.len()) })
+    }
+}
+
+impl From<Vec<i64>> for Value {
+    fn from(value: Vec<i64>) -> Value {
+        Value::from(value.as_slice())
+    }
+}
+
+// ____________________________________________________________
+// Value to native types
+
+/// The error returned when a [`Value`] holds a different kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TryFromValueError {
+    pub expected: ValueKind,
+    pub found: ValueKind,
+}
+
+impl Error for TryFromValueError {}
+
+impl fmt::Display for TryFromValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected {:?} value, found {:?}",
+            self.expected, self.found
+        )
+    }
+}
+
+macro_rules! impl_try_from_value {
+    ($type:ty, $kind:ident, $accessor:ident, $convert:expr) => {
+        impl TryFrom<&Value> for $type {
+            type Error = TryFromValueError;
+
+            fn try_from(value: &Value) -> Result<$type, TryFromValueError> {
+                value.$accessor().map($convert).ok_or(TryFromValueError {
+                    expected: ValueKind::$kind,
+                    found: value.kind(),
+                })
+            }
+        }
+
+        impl TryFrom<Value> for $type {
+            type Error = TryFromValueError;
+
+            fn try_from(value: Value) -> Result<$type, TryFromValueError> {
+                <$type>::try_from(&value)
+            }
+        }
+    };
+}
+
+impl_try_from_value!(i32, Int32, as_i32, |x| x);
+impl_try_from_value!(bool, Bool, as_bool, |x| x);
+impl_try_from_value!(f64, Double, as_f64, |x| x);
+impl_try_from_value!(String, String, as_str, str::to_owned);
+impl_try_from_value!(Vec<i64>, Int64Array, as_slice, <[i64]>::to_vec);
+
+// ____________________________________________________________
+// Fill strategies
+
+/// A way of filling a buffer through one of the `NLFill` functions.
+///
+/// # Safety
+/// `fill_uninit` must initialize every element of `buffer`.
+pub unsafe trait FillStrategy {
+    fn fill_uninit(self, buffer: &mut [MaybeUninit<i64>]);
+}
+
+/// `start, start + step, start + 2 * step, ...`, wrapping on overflow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub start: i64,
+    pub step: i64,
+}
+
+impl Default for Range {
+    /// The `0, 1, 2, ...` sequence written by `NLInitVector`.
+    fn default() -> Range {
+        Range { start: 0, step: 1 }
+    }
+}
+
+unsafe impl FillStrategy for Range {
+    fn fill_uninit(self, buffer: &mut [MaybeUninit<i64>]) {
+        unsafe {
+            ffi::NLFillRange(
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+                self.start,
+                self.step,
+            )
+        }
+    }
+}
+
+/// The same value for every element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Constant(pub i64);
+
+unsafe impl FillStrategy for Constant {
+    fn fill_uninit(self, buffer: &mut [MaybeUninit<i64>]) {
+        unsafe { ffi::NLFillConstant(buffer.as_mut_ptr().cast(), buffer.len(), self.0) }
+    }
+}
+
+/// The value returned by the closure for each index, called from C.
+///
+/// A panic in the closure stops further calls and is resumed once
+/// `NLFillGenerate` returns.
+pub struct Generate<F: FnMut(usize) -> i64>(pub F);
+
+unsafe impl<F: FnMut(usize) -> i64> FillStrategy for Generate<F> {
+    fn fill_uninit(self, buffer: &mut [MaybeUninit<i64>]) {
+        struct Context<F> {
+            generator: F,
+            panic: Option<Box<dyn Any + Send>>,
+        }
+
+        extern "C" fn generate<F: FnMut(usize) -> i64>(context: *mut c_void, index: usize) -> i64 {
+            let context = unsafe { &mut *context.cast::<Context<F>>() };
+            if context.panic.is_some() {
+                return 0;
+            }
+            // Unwinding into C is not allowed
+            match panic::catch_unwind(AssertUnwindSafe(|| (context.generator)(index))) {
+                Ok(value) => value,
+                Err(payload) => {
+                    context.panic = Some(payload);
+                    0
+                }
+            }
+        }
+
+        let mut context = Context {
+            generator: self.0,
+            panic: None,
+        };
+        unsafe {
+            ffi::NLFillGenerate(
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+                ptr::addr_of_mut!(context).cast(),
+                generate::<F>,
+            )
+        }
+        if let Some(payload) = context.panic {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+/// Fills `buffer` and returns it as initialized.
+///
+/// # Examples
+/// ```
+/// use lib::clib::{fill, Constant};
+/// use std::mem::MaybeUninit;
+///
+/// let mut buffer = [MaybeUninit::uninit(); 3];
+/// assert_eq!(fill(&mut buffer, Constant(7)), [7, 7, 7]);
+/// ```
+pub fn fill<S: FillStrategy>(buffer: &mut [MaybeUninit<i64>], strategy: S) -> &mut [i64] {
+    strategy.fill_uninit(buffer);
+    // The strategy initialized every element.
+    unsafe { &mut *(buffer as *mut [MaybeUninit<i64>] as *mut [i64]) }
+}
+
+/// A vector of `count` elements filled by `strategy`.
+///
+/// # Examples
+/// ```
+/// use lib::clib::{fill_vec, Generate, Range};
+///
+/// assert_eq!(fill_vec(3, Range::default()), [0, 1, 2]);
+/// assert_eq!(fill_vec(3, Range { start: 10, step: -5 }), [10, 5, 0]);
+/// assert_eq!(fill_vec(3, Generate(|i| (i * i) as i64)), [0, 1, 4]);
+/// ```
+pub fn fill_vec<S: FillStrategy>(count: usize, strategy: S) -> Vec<i64> {
+    let mut vector = Vec::with_capacity(count);
+    fill(&mut vector.spare_capacity_mut()[..count], strategy);
+    unsafe { vector.set_len(count) }
+    vector
+}
+
+// ____________________________________________________________
+// Layouts
+
+/// The layouts of the types shared with Rust, as compiled by `build.rs`.
+///
+/// Fields have the C names and types, and no niches. Enums are opaque.
+pub fn c_layouts() -> Vec<Layout> {
+    // The C layouts are static arrays of string literals
+    fn string(pointer: *const c_char) -> &'static str {
+        unsafe { CStr::from_ptr(pointer) }.to_str().unwrap()
+    }
+
+    let mut count = 0;
+    let layouts = unsafe { slice::from_raw_parts(ffi::NLTypeLayouts(&mut count), count) };
+    layouts
+        .iter()
+        .map(|layout| {
+            let fields = match layout.field_count {
+                0 => &[],
+                count => unsafe { slice::from_raw_parts(layout.fields, count) },
+            };
+            Layout {
+                name: string(layout.name),
+                size: layout.size,
+                align: layout.align,
+                fields: fields
+                    .iter()
+                    .map(|field| FieldLayout {
+                        name: string(field.name),
+                        type_name: string(field.r#type),
+                        offset: field.offset,
+                        size: field.size,
+                        has_niche: false,
+                        niche: Vec::new(),
+                    })
+                    .collect(),
+                // Enums
+                opaque: layout.field_count == 0,
+                tag: None,
+                has_niche: false,
+                niche: Vec::new(),
+            }
+        })
+        .collect()
+}
+
+/// The layouts of the Rust types mirroring C types, with the C layouts.
+///
+/// # Examples
+/// ```
+/// for (rust, c) in lib::clib::mirrored_layouts() {
+///     assert_eq!(rust.compare(&c), [], "{rust}\n{c}");
+/// }
+/// ```
+pub fn mirrored_layouts() -> Vec<(Layout, Layout)> {
+    let mirrors = [
+        ("NLVariantKind", Layout::of::<ValueKind>()),
+        ("NLString", Layout::of::<ffi::NLString>()),
+        ("NLInt64Array", Layout::of::<ffi::NLInt64Array>()),
+        ("NLVariant", Layout::of::<ffi::NLVariant>()),
+    ];
+    let c_layouts = c_layouts();
+    mirrors
+        .into_iter()
+        .filter_map(|(name, rust)| {
+            let c = c_layouts.iter().find(|layout| layout.name == name)?;
+            Some((rust, c.clone()))
+        })
+        .collect()
+}
@@ -0,0 +1,183 @@
+//! Runtime descriptions of types, usually implemented with `#[derive(Describe)]`.
+
+pub use local_macro::Describe;
+
+use std::ops::Range;
+
+/// The derive generates, for structs and enums, the type name, size,
+/// alignment and niche, the fields with their types, sizes,
+/// `mem::offset_of!` offsets and niche bytes, and the variants with their
+/// fields.
+///
+/// # Examples
+/// ```
+/// use lib::describe::{Describe, TypeKind};
+///
+/// #[derive(Describe)]
+/// struct Point {
+///     x: u8,
+///     y: u32,
+/// }
+///
+/// let description = Point::describe();
+/// assert_eq!(description.kind, TypeKind::Struct);
+/// assert_eq!(description.field("y").unwrap().type_name, "u32");
+/// ```
+pub trait Describe {
+    fn describe() -> TypeDescription;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeKind {
+    Struct,
+    Enum,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeDescription {
+    pub name: &'static str,
+    pub kind: TypeKind,
+    pub size: usize,
+    pub align: usize,
+    /// Whether some bit patterns are invalid, and so usable by enclosing
+    /// enums as tags, as the null pointer of `Option<&T>`
+    pub niche: bool,
+    /// The bytes of the tag of enums, when fixed by their `repr` or when all
+    /// their variants are units
+    pub tag: Option<Range<usize>>,
+    /// Empty for enums
+    pub fields: Vec<FieldDescription>,
+    /// Empty for structs
+    pub variants: Vec<VariantDescription>,
+}
+
+impl TypeDescription {
+    pub fn field(&self, name: &str) -> Option<&FieldDescription> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    pub fn variant(&self, name: &str) -> Option<&VariantDescription> {
+        self.variants.iter().find(|variant| variant.name == name)
+    }
+
+    /// The bytes known to hold the niche: the tag of enums, and the niche
+    /// bytes of the fields of structs.
+    pub fn niche_bytes(&self) -> Vec<Range<usize>> {
+        if !self.niche {
+            return Vec::new();
+        }
+        if self.kind == TypeKind::Enum {
+            return self.tag.clone().into_iter().collect();
+        }
+        let mut bytes: Vec<Range<usize>> = self
+            .fields
+            .iter()
+            .filter_map(|field| Some((field.offset?, &field.niche_bytes)))
+            .flat_map(|(offset, bytes)| {
+                bytes
+                    .iter()
+                    .map(move |range| offset + range.start..offset + range.end)
+            })
+            .collect();
+        bytes.sort_by_key(|range| range.start);
+        bytes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDescription {
+    /// The index for tuple fields
+    pub name: &'static str,
+    pub type_name: &'static str,
+    /// `None` for the fields of enum variants
+    pub offset: Option<usize>,
+    pub size: usize,
+    /// As [`TypeDescription::niche`]
+    pub niche: bool,
+    /// The bytes of the field holding its niche, empty if it has none or if
+    /// they are unknown, as for the fields of generic types
+    pub niche_bytes: Vec<Range<usize>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariantDescription {
+    pub name: &'static str,
+    pub fields: Vec<FieldDescription>,
+}
+
+// ____________________________________________________________
+// Niche detection
+
+// Autoref specialization, as for the traits of the registry: called on
+// `&&&Probe<T>`, `Described` applies first, then `Scalar`, then `Unknown`.
+
+#[doc(hidden)]
+pub struct Probe<T: ?Sized>(pub std::marker::PhantomData<T>);
+
+#[doc(hidden)]
+pub mod probe {
+    use super::{Describe, Probe};
+    use std::num::*;
+    use std::ops::Range;
+    use std::ptr::NonNull;
+
+    pub trait Described {
+        fn niche_bytes(&self) -> Vec<Range<usize>>;
+    }
+
+    impl<T: Describe> Described for &&Probe<T> {
+        fn niche_bytes(&self) -> Vec<Range<usize>> {
+            T::describe().niche_bytes()
+        }
+    }
+
+    /// The scalars with invalid values, whose niche is the whole scalar.
+    pub trait Scalar {
+        fn niche_bytes(&self) -> Vec<Range<usize>>;
+    }
+
+    macro_rules! scalars {
+        ($([$($generics:tt)*] $ty:ty,)*) => {
+            $(
+                impl<$($generics)*> Scalar for &Probe<$ty> {
+                    fn niche_bytes(&self) -> Vec<Range<usize>> {
+                        vec![0..std::mem::size_of::<$ty>()]
+                    }
+                }
+            )*
+        };
+    }
+
+    // Only pointers to sized types, the layout of wide pointers being
+    // unspecified
+    scalars! {
+        [] bool,
+        [] char,
+        [] NonZeroU8,
+        [] NonZeroU16,
+        [] NonZeroU32,
+        [] NonZeroU64,
+        [] NonZeroU128,
+        [] NonZeroUsize,
+        [] NonZeroI8,
+        [] NonZeroI16,
+        [] NonZeroI32,
+        [] NonZeroI64,
+        [] NonZeroI128,
+        [] NonZeroIsize,
+        ['a, T] &'a T,
+        ['a, T] &'a mut T,
+        [T] Box<T>,
+        [T] NonNull<T>,
+    }
+
+    pub trait Unknown {
+        fn niche_bytes(&self) -> Vec<Range<usize>>;
+    }
+
+    impl<T: ?Sized> Unknown for Probe<T> {
+        fn niche_bytes(&self) -> Vec<Range<usize>> {
+            Vec::new()
+        }
+    }
+}
@@ -0,0 +1,308 @@
+//! Error handling helpers.
+
+use std::any::Any;
+use std::convert::Infallible;
+use std::error::Error as StdError;
+use std::fmt;
+use std::ops::Deref;
+
+/// Implements `Display`, `std::error::Error` and `From` for a struct or an
+/// enum, as written by hand for `HighLevelError` in `tests/r_18_errors.rs`.
+///
+/// - `#[error("...")]` on the struct or on each variant is the `Display`
+///   format string, interpolating fields as `{name}` or `{0}`.
+/// - `#[error(transparent)]` forwards `Display` and `source` to the only
+///   field.
+/// - `#[source]` marks the field returned by `source`, as does a field
+///   named `source`.
+/// - `#[from]` also marks the source and implements `From` for its type.
+///   The field must be the only one, and two variants can't be converted
+///   from the same type.
+///
+/// # Examples
+/// ```
+/// use lib::error::Error;
+/// use std::error::Error as _;
+/// use std::io;
+///
+/// #[derive(Debug, Error)]
+/// enum ConfigError {
+///     #[error("cannot read {path}")]
+///     Read { path: String, source: io::Error },
+///     #[error("invalid value at line {0}")]
+///     Invalid(usize),
+///     #[error(transparent)]
+///     Other(#[from] io::Error),
+/// }
+///
+/// let error = ConfigError::Read {
+///     path: "config.toml".into(),
+///     source: io::Error::new(io::ErrorKind::NotFound, "not found"),
+/// };
+/// assert_eq!(error.to_string(), "cannot read config.toml");
+/// assert_eq!(error.source().unwrap().to_string(), "not found");
+/// assert_eq!(ConfigError::Invalid(3).to_string(), "invalid value at line 3");
+///
+/// let error = ConfigError::from(io::Error::other("disk full"));
+/// assert_eq!(error.to_string(), "disk full");
+/// assert!(error.source().is_none());
+/// ```
+///
+/// ```compile_fail
+/// #[derive(Debug, lib::error::Error)]
+/// enum ParseError {
+///     #[error("invalid integer")]
+///     Integer(#[from] std::num::ParseIntError),
+///     #[error("invalid port")]
+///     Port(#[from] std::num::ParseIntError),
+/// }
+/// ```
+pub use local_macro::Error;
+
+/// Borrows an error as a trait object, for `#[derive(Error)]`.
+///
+/// Implemented for errors and for `dyn Error`, so that boxed sources, which
+/// don't implement `Error` themselves, are found through auto-deref.
+#[doc(hidden)]
+pub trait AsDynError<'a> {
+    fn as_dyn_error(&self) -> &(dyn StdError + 'a);
+}
+
+impl<'a, E: StdError + 'a> AsDynError<'a> for E {
+    fn as_dyn_error(&self) -> &(dyn StdError + 'a) {
+        self
+    }
+}
+
+impl<'a> AsDynError<'a> for dyn StdError + 'a {
+    fn as_dyn_error(&self) -> &(dyn StdError + 'a) {
+        self
+    }
+}
+
+impl<'a> AsDynError<'a> for dyn StdError + Send + 'a {
+    fn as_dyn_error(&self) -> &(dyn StdError + 'a) {
+        self
+    }
+}
+
+impl<'a> AsDynError<'a> for dyn StdError + Send + Sync + 'a {
+    fn as_dyn_error(&self) -> &(dyn StdError + 'a) {
+        self
+    }
+}
+
+// ____________________________________________________________
+// Context
+
+/// Attaches a message to the error of a `Result` or to the `None` of an
+/// `Option`, keeping the original error as its source.
+///
+/// # Examples
+/// ```
+/// use lib::error::Context;
+/// use std::error::Error;
+///
+/// let error = "x".parse::<i32>().context("invalid port").unwrap_err();
+/// assert_eq!(error.to_string(), "invalid port");
+/// assert_eq!(error.source().unwrap().to_string(), "invalid digit found in string");
+///
+/// let name = std::env::args().nth(100).with_context(|| format!("missing argument {}", 100));
+/// assert_eq!(name.unwrap_err().to_string(), "missing argument 100");
+/// ```
+pub trait Context<T, E> {
+    fn context<C: fmt::Display>(self, context: C) -> Result<T, ContextError<C, E>>;
+
+    /// Only calls `context` on errors.
+    fn with_context<C: fmt::Display, F: FnOnce() -> C>(
+        self,
+        context: F,
+    ) -> Result<T, ContextError<C, E>>;
+}
+
+impl<T, E: StdError> Context<T, E> for Result<T, E> {
+    fn context<C: fmt::Display>(self, context: C) -> Result<T, ContextError<C, E>> {
+        self.map_err(|error| ContextError::new(context, error))
+    }
+
+    fn with_context<C: fmt::Display, F: FnOnce() -> C>(
+        self,
+        context: F,
+    ) -> Result<T, ContextError<C, E>> {
+        self.map_err(|error| ContextError::new(context(), error))
+    }
+}
+
+/// `None` has no source, hence the `Infallible` error.
+impl<T> Context<T, Infallible> for Option<T> {
+    fn context<C: fmt::Display>(self, context: C) -> Result<T, ContextError<C, Infallible>> {
+        self.ok_or_else(|| ContextError::without_source(context))
+    }
+
+    fn with_context<C: fmt::Display, F: FnOnce() -> C>(
+        self,
+        context: F,
+    ) -> Result<T, ContextError<C, Infallible>> {
+        self.ok_or_else(|| ContextError::without_source(context()))
+    }
+}
+
+/// A message displayed instead of the error it wraps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextError<C, E> {
+    context: C,
+    error: Option<E>,
+}
+
+impl<C, E> ContextError<C, E> {
+    pub fn new(context: C, error: E) -> ContextError<C, E> {
+        ContextError {
+            context,
+            error: Some(error),
+        }
+    }
+
+    pub fn without_source(context: C) -> ContextError<C, E> {
+        ContextError {
+            context,
+            error: None,
+        }
+    }
+
+    pub fn context(&self) -> &C {
+        &self.context
+    }
+
+    pub fn error(&self) -> Option<&E> {
+        self.error.as_ref()
+    }
+
+    pub fn into_error(self) -> Option<E> {
+        self.error
+    }
+}
+
+impl<C: fmt::Display, E> fmt::Display for ContextError<C, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.context, f)
+    }
+}
+
+impl<C: fmt::Display + fmt::Debug, E: StdError + 'static> StdError for ContextError<C, E> {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.error
+            .as_ref()
+            .map(|error| error as &(dyn StdError + 'static))
+    }
+}
+
+// ____________________________________________________________
+// AnyError
+
+trait Erased: StdError + Any + Send + Sync {}
+
+impl<E: StdError + Any + Send + Sync> Erased for E {}
+
+/// Any error, for functions that only propagate errors.
+///
+/// As `Box<dyn Error>`, it is created with `?` from any error, but it can
+/// also be downcast back to the original error, as `Box<dyn Any>`. It doesn't
+/// implement `Error` itself, as that would conflict with `From<E: Error>`,
+/// but it derefs to `dyn Error`.
+///
+/// # Examples
+/// ```
+/// use lib::error::{AnyError, Context, ContextError};
+/// use std::num::ParseIntError;
+///
+/// fn parse(text: &str) -> Result<i32, AnyError> {
+///     let value = text.parse::<i32>().context("invalid number")?;
+///     Ok(value)
+/// }
+///
+/// let error = parse("x").unwrap_err();
+/// assert_eq!(error.to_string(), "invalid number");
+/// assert!(error.is::<ContextError<&str, ParseIntError>>());
+/// assert!(error.find::<ParseIntError>().is_some());
+/// assert!(error.downcast::<ParseIntError>().is_err());
+/// ```
+pub struct AnyError(Box<dyn Erased>);
+
+impl AnyError {
+    pub fn new<E: StdError + Send + Sync + 'static>(error: E) -> AnyError {
+        AnyError(Box::new(error))
+    }
+
+    /// Whether the error is an `E`, ignoring its sources.
+    pub fn is<E: 'static>(&self) -> bool {
+        (&*self.0 as &dyn Any).is::<E>()
+    }
+
+    pub fn downcast_ref<E: 'static>(&self) -> Option<&E> {
+        (&*self.0 as &dyn Any).downcast_ref()
+    }
+
+    pub fn downcast_mut<E: 'static>(&mut self) -> Option<&mut E> {
+        (&mut *self.0 as &mut dyn Any).downcast_mut()
+    }
+
+    /// The original error, or `self` if it isn't an `E`.
+    pub fn downcast<E: 'static>(self) -> Result<E, AnyError> {
+        if self.is::<E>() {
+            let error: Box<dyn Any> = self.0;
+            Ok(*error.downcast().unwrap())
+        } else {
+            Err(self)
+        }
+    }
+
+    /// The error followed by its sources.
+    pub fn chain(&self) -> impl Iterator<Item = &(dyn StdError + 'static)> {
+        std::iter::successors(Some(&**self as &(dyn StdError + 'static)), |&error| {
+            error.source()
+        })
+    }
+
+    /// The first `E` in the chain.
+    pub fn find<E: StdError + 'static>(&self) -> Option<&E> {
+        self.chain().find_map(|error| error.downcast_ref())
+    }
+}
+
+impl<E: StdError + Send + Sync + 'static> From<E> for AnyError {
+    fn from(error: E) -> AnyError {
+        AnyError::new(error)
+    }
+}
+
+impl Deref for AnyError {
+    type Target = dyn StdError + Send + Sync + 'static;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+impl From<AnyError> for Box<dyn StdError + Send + Sync + 'static> {
+    fn from(error: AnyError) -> Self {
+        error.0
+    }
+}
+
+impl From<AnyError> for Box<dyn StdError + 'static> {
+    fn from(error: AnyError) -> Self {
+        error.0
+    }
+}
+
+impl fmt::Display for AnyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Debug for AnyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
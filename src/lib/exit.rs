@@ -0,0 +1,115 @@
+//! Exit codes of error types, for `main`.
+//!
+//! Applications [`register`] their error types against the `sysexits.h`
+//! codes of [`code`] and return an [`Exit`] from `main`.
+
+use crate::report::Report;
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::error::Error;
+use std::process::{ExitCode, Termination};
+use std::sync::{LazyLock, RwLock};
+
+/// The codes of `sysexits.h`.
+pub mod code {
+    pub const OK: u8 = 0;
+    /// Not a `sysexits.h` code, returned for unregistered errors
+    pub const FAILURE: u8 = 1;
+    pub const USAGE: u8 = 64;
+    pub const DATAERR: u8 = 65;
+    pub const NOINPUT: u8 = 66;
+    pub const NOUSER: u8 = 67;
+    pub const NOHOST: u8 = 68;
+    pub const UNAVAILABLE: u8 = 69;
+    pub const SOFTWARE: u8 = 70;
+    pub const OSERR: u8 = 71;
+    pub const OSFILE: u8 = 72;
+    pub const CANTCREAT: u8 = 73;
+    pub const IOERR: u8 = 74;
+    pub const TEMPFAIL: u8 = 75;
+    pub const PROTOCOL: u8 = 76;
+    pub const NOPERM: u8 = 77;
+    pub const CONFIG: u8 = 78;
+}
+
+struct Entry {
+    /// `dyn Error` has no public `TypeId`, so errors are matched by downcasting
+    matches: fn(&(dyn Error + 'static)) -> bool,
+    code: u8,
+}
+
+static REGISTRY: LazyLock<RwLock<HashMap<TypeId, Entry>>> = LazyLock::new(Default::default);
+
+/// Maps the errors of type `E` to `code`, returning the previous code.
+pub fn register<E: Error + 'static>(code: u8) -> Option<u8> {
+    let entry = Entry {
+        matches: |error| error.is::<E>(),
+        code,
+    };
+    let mut registry = REGISTRY.write().unwrap_or_else(|error| error.into_inner());
+    registry
+        .insert(TypeId::of::<E>(), entry)
+        .map(|entry| entry.code)
+}
+
+pub fn unregister<E: Error + 'static>() -> Option<u8> {
+    let mut registry = REGISTRY.write().unwrap_or_else(|error| error.into_inner());
+    registry.remove(&TypeId::of::<E>()).map(|entry| entry.code)
+}
+
+/// The code of the most specific registered error of the `source()` chain,
+/// the one closest to the root cause.
+pub fn code_of(error: &(dyn Error + 'static)) -> Option<u8> {
+    let registry = REGISTRY.read().unwrap_or_else(|error| error.into_inner());
+    std::iter::successors(Some(error), |&error| error.source())
+        .filter_map(|error| {
+            registry
+                .values()
+                .find(|entry| (entry.matches)(error))
+                .map(|entry| entry.code)
+        })
+        .last()
+}
+
+/// A `Result` returned from `main`.
+///
+/// On `Err`, prints the [`Report`] of the error to stderr and exits with its
+/// registered code, or [`code::FAILURE`].
+///
+/// # Examples
+/// ```no_run
+/// use lib::exit::{self, code, Exit};
+/// use std::num::ParseIntError;
+///
+/// fn run() -> Result<(), ParseIntError> {
+///     let port: u16 = std::env::args().nth(1).unwrap_or_default().parse()?;
+///     println!("port {port}");
+///     Ok(())
+/// }
+///
+/// fn main() -> Exit<(), ParseIntError> {
+///     exit::register::<ParseIntError>(code::USAGE);
+///     Exit(run())
+/// }
+/// ```
+#[must_use]
+pub struct Exit<T, E>(pub Result<T, E>);
+
+impl<T, E> From<Result<T, E>> for Exit<T, E> {
+    fn from(result: Result<T, E>) -> Exit<T, E> {
+        Exit(result)
+    }
+}
+
+impl<T: Termination, E: Into<Box<dyn Error + 'static>>> Termination for Exit<T, E> {
+    fn report(self) -> ExitCode {
+        match self.0 {
+            Ok(value) => value.report(),
+            Err(error) => {
+                let report = Report::with_backtrace(error);
+                eprintln!("{report:#}");
+                ExitCode::from(code_of(report.error()).unwrap_or(code::FAILURE))
+            }
+        }
+    }
+}
@@ -0,0 +1,388 @@
+//! Measures of the quality and speed of a hash function, to compare hashers
+//! before adopting one.
+//!
+//! - avalanche: flipping one bit of a key should flip each bit of the hash
+//!   with a probability of 1/2
+//! - distribution: the keys of a [`KeySet`] should fill the buckets of a
+//!   hash table evenly, measured with a chi-square test on the low bits of
+//!   the hashes, which `HashMap` uses as bucket indices
+//! - collisions: the keys hashing to a hash already seen
+//! - throughput: bytes hashed per second on a long key, and short keys
+//!   hashed per second
+//!
+//! Keys are generated from a fixed seed, so results other than throughput
+//! are reproducible.
+//!
+//! # Examples
+//! ```
+//! use lib::hash::analysis::{self, Options};
+//! use lib::hash::BuildFnv1a64;
+//! use std::time::Duration;
+//!
+//! let options = Options {
+//!     duration: Duration::from_millis(1),
+//!     ..Options::default()
+//! };
+//! let analysis = analysis::analyze("fnv1a64", &BuildFnv1a64::default(), &options);
+//! assert!(analysis.distributions.iter().all(|distribution| distribution.collisions == 0));
+//! println!("{}", analysis::table(&[analysis]));
+//! ```
+
+use crate::report::write_json_string;
+use std::collections::HashSet;
+use std::fmt::{self, Write};
+use std::hash::{BuildHasher, Hash};
+use std::hint::black_box;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    /// Keys per key set
+    pub keys: usize,
+    /// Keys whose bits are flipped one at a time in the avalanche test
+    pub avalanche_samples: usize,
+    /// The width of the hashes, 32 for hashers returning a `u32` in a `u64`
+    pub bits: u32,
+    /// Of each throughput measure
+    pub duration: Duration,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            keys: 1 << 16,
+            avalanche_samples: 1000,
+            bits: 64,
+            duration: Duration::from_millis(100),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeySet {
+    /// `u64`s from 0
+    Sequential,
+    /// Strings of 1 to 4 lowercase letters: `a`, .., `z`, `aa`, ..
+    ShortStrings,
+    /// IPv4 socket addresses of a `10.0.0.0/16` network, then on other ports
+    Structured,
+}
+
+impl KeySet {
+    pub const ALL: &'static [KeySet] =
+        &[KeySet::Sequential, KeySet::ShortStrings, KeySet::Structured];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            KeySet::Sequential => "sequential",
+            KeySet::ShortStrings => "short strings",
+            KeySet::Structured => "structured",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    pub hasher: String,
+    pub avalanche: Avalanche,
+    /// One per key set of [`KeySet::ALL`]
+    pub distributions: Vec<Distribution>,
+    pub throughput: Throughput,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Avalanche {
+    pub samples: usize,
+    /// The probability of an output bit to flip, ideally 0.5
+    pub mean: f64,
+    /// The largest `|2p - 1|` over the pairs of input and output bits, `p`
+    /// being the probability that flipping the input bit flips the output
+    /// bit: 0 is ideal, 1 means that an output bit never or always flips
+    pub worst_bias: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution {
+    pub key_set: KeySet,
+    pub keys: usize,
+    pub buckets: usize,
+    pub collisions: usize,
+    pub chi_square: f64,
+    /// The chi-square normalized by its `buckets - 1` degrees of freedom,
+    /// within a few units of 0 for a uniform distribution
+    pub z_score: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Throughput {
+    pub bytes_per_second: f64,
+    pub keys_per_second: f64,
+}
+
+/// Runs every measure on the hashers built by `build`.
+pub fn analyze<B: BuildHasher>(name: impl Into<String>, build: &B, options: &Options) -> Analysis {
+    let mask = match options.bits {
+        64.. => u64::MAX,
+        bits => (1 << bits) - 1,
+    };
+    let distributions = KeySet::ALL
+        .iter()
+        .map(|&key_set| {
+            let hashes: Vec<u64> = match key_set {
+                KeySet::Sequential => hash_keys(build, mask, 0..options.keys as u64),
+                KeySet::ShortStrings => hash_keys(build, mask, (0..options.keys).map(short_string)),
+                KeySet::Structured => hash_keys(build, mask, (0..options.keys).map(socket_address)),
+            };
+            distribution(key_set, &hashes)
+        })
+        .collect();
+
+    Analysis {
+        hasher: name.into(),
+        avalanche: avalanche(options, |key| build.hash_one(key) & mask),
+        distributions,
+        throughput: throughput(build, options.duration),
+    }
+}
+
+fn hash_keys<B: BuildHasher, K: Hash>(
+    build: &B,
+    mask: u64,
+    keys: impl Iterator<Item = K>,
+) -> Vec<u64> {
+    keys.map(|key| build.hash_one(key) & mask).collect()
+}
+
+/// `a` to `z`, then `aa` to `zz` and so on.
+fn short_string(mut index: usize) -> String {
+    let mut string = String::new();
+    loop {
+        string.push(char::from(b'a' + (index % 26) as u8));
+        index /= 26;
+        if index == 0 {
+            return string;
+        }
+        index -= 1;
+    }
+}
+
+fn socket_address(index: usize) -> SocketAddrV4 {
+    let [.., port, network, host] = (index as u32).to_be_bytes();
+    SocketAddrV4::new(Ipv4Addr::new(10, 0, network, host), 8000 + u16::from(port))
+}
+
+/// A pseudo-random generator, SplitMix64.
+fn random(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn avalanche(options: &Options, hash: impl Fn(u64) -> u64) -> Avalanche {
+    let samples = options.avalanche_samples.max(1);
+    let bits = options.bits.min(64) as usize;
+    // Flips of each output bit for each input bit
+    let mut flips = vec![[0u32; 64]; 64];
+    let mut state = 0;
+    for _ in 0..samples {
+        let key = random(&mut state);
+        let original = hash(key);
+        for (input, flips) in flips.iter_mut().enumerate() {
+            let difference = original ^ hash(key ^ (1 << input));
+            for (output, flips) in flips[..bits].iter_mut().enumerate() {
+                *flips += (difference >> output & 1) as u32;
+            }
+        }
+    }
+
+    let probabilities: Vec<f64> = flips
+        .iter()
+        .flat_map(|flips| &flips[..bits])
+        .map(|&flips| f64::from(flips) / samples as f64)
+        .collect();
+    Avalanche {
+        samples,
+        mean: probabilities.iter().sum::<f64>() / probabilities.len().max(1) as f64,
+        worst_bias: probabilities
+            .iter()
+            .map(|probability| (2.0 * probability - 1.0).abs())
+            .fold(0.0, f64::max),
+    }
+}
+
+fn distribution(key_set: KeySet, hashes: &[u64]) -> Distribution {
+    let keys = hashes.len();
+    // About 8 keys per bucket, a power of two to use the low bits
+    let buckets = (keys / 8).max(2).next_power_of_two();
+    let mut counts = vec![0usize; buckets];
+    for hash in hashes {
+        counts[*hash as usize & (buckets - 1)] += 1;
+    }
+    let expected = keys as f64 / buckets as f64;
+    let chi_square: f64 = counts
+        .iter()
+        .map(|&count| (count as f64 - expected).powi(2) / expected)
+        .sum();
+    let freedom = (buckets - 1) as f64;
+
+    let distinct: HashSet<u64> = hashes.iter().copied().collect();
+    Distribution {
+        key_set,
+        keys,
+        buckets,
+        collisions: keys - distinct.len(),
+        chi_square,
+        z_score: (chi_square - freedom) / (2.0 * freedom).sqrt(),
+    }
+}
+
+fn throughput<B: BuildHasher>(build: &B, duration: Duration) -> Throughput {
+    let block = vec![0xa5u8; 1 << 16];
+    let start = Instant::now();
+    let mut bytes = 0;
+    while start.elapsed() < duration || bytes == 0 {
+        black_box(build.hash_one(black_box(&block[..])));
+        bytes += block.len();
+    }
+    let bytes_per_second = bytes as f64 / start.elapsed().as_secs_f64();
+
+    let start = Instant::now();
+    let mut keys = 0u64;
+    while start.elapsed() < duration || keys == 0 {
+        // Checking the time every 1024 keys only
+        for key in keys..keys + 1024 {
+            black_box(build.hash_one(black_box(key)));
+        }
+        keys += 1024;
+    }
+    Throughput {
+        bytes_per_second,
+        keys_per_second: keys as f64 / start.elapsed().as_secs_f64(),
+    }
+}
+
+// ____________________________________________________________
+// Formats
+
+/// One row per analysis, each key set showing the z-score of its
+/// distribution and its collisions in parentheses.
+pub fn table(analyses: &[Analysis]) -> Table<'_> {
+    Table(analyses)
+}
+
+/// An array of objects with the fields of [`Analysis`].
+pub fn json(analyses: &[Analysis]) -> Json<'_> {
+    Json(analyses)
+}
+
+pub struct Table<'a>(&'a [Analysis]);
+
+impl fmt::Display for Table<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut header = vec!["hasher", "avalanche", "bias"];
+        header.extend(KeySet::ALL.iter().map(|key_set| key_set.name()));
+        header.extend(["MB/s", "Mkeys/s"]);
+        let mut rows = vec![header.into_iter().map(String::from).collect::<Vec<_>>()];
+        for analysis in self.0 {
+            let mut row = vec![
+                analysis.hasher.clone(),
+                format!("{:.3}", analysis.avalanche.mean),
+                format!("{:.3}", analysis.avalanche.worst_bias),
+            ];
+            for distribution in &analysis.distributions {
+                row.push(format!(
+                    "{:.1} ({})",
+                    distribution.z_score, distribution.collisions
+                ));
+            }
+            row.push(format!("{:.0}", analysis.throughput.bytes_per_second / 1e6));
+            row.push(format!("{:.1}", analysis.throughput.keys_per_second / 1e6));
+            rows.push(row);
+        }
+
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..columns)
+            .map(|column| {
+                rows.iter()
+                    .filter_map(|row| row.get(column))
+                    .map(|cell| cell.chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        for (index, row) in rows.iter().enumerate() {
+            if index > 0 {
+                f.write_char('\n')?;
+            }
+            let mut line = String::new();
+            for (column, cell) in row.iter().enumerate() {
+                let width = widths[column];
+                match column {
+                    0 => write!(line, "{cell:<width$}")?,
+                    _ => write!(line, "  {cell:>width$}")?,
+                }
+            }
+            f.write_str(line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Json<'a>(&'a [Analysis]);
+
+impl fmt::Display for Json<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char('[')?;
+        for (index, analysis) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_char(',')?;
+            }
+            let avalanche = &analysis.avalanche;
+            f.write_str("{\"hasher\":")?;
+            write_json_string(f, &analysis.hasher)?;
+            write!(
+                f,
+                ",\"avalanche\":{{\"samples\":{},\"mean\":",
+                avalanche.samples
+            )?;
+            write_json_number(f, avalanche.mean)?;
+            f.write_str(",\"worst_bias\":")?;
+            write_json_number(f, avalanche.worst_bias)?;
+            f.write_str("},\"distributions\":[")?;
+            for (index, distribution) in analysis.distributions.iter().enumerate() {
+                if index > 0 {
+                    f.write_char(',')?;
+                }
+                f.write_str("{\"key_set\":")?;
+                write_json_string(f, distribution.key_set.name())?;
+                write!(
+                    f,
+                    ",\"keys\":{},\"buckets\":{},\"collisions\":{},\"chi_square\":",
+                    distribution.keys, distribution.buckets, distribution.collisions
+                )?;
+                write_json_number(f, distribution.chi_square)?;
+                f.write_str(",\"z_score\":")?;
+                write_json_number(f, distribution.z_score)?;
+                f.write_char('}')?;
+            }
+            f.write_str("],\"throughput\":{\"bytes_per_second\":")?;
+            write_json_number(f, analysis.throughput.bytes_per_second)?;
+            f.write_str(",\"keys_per_second\":")?;
+            write_json_number(f, analysis.throughput.keys_per_second)?;
+            f.write_str("}}")?;
+        }
+        f.write_char(']')
+    }
+}
+
+/// JSON has no infinities nor NaN.
+fn write_json_number(f: &mut fmt::Formatter<'_>, number: f64) -> fmt::Result {
+    if number.is_finite() {
+        write!(f, "{number}")
+    } else {
+        f.write_str("null")
+    }
+}
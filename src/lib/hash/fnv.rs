@@ -0,0 +1,72 @@
+//! FNV-1a, which xors each byte into the state, then multiplies it by a prime.
+
+use std::hash::{BuildHasherDefault, Hasher};
+
+const OFFSET_BASIS_32: u32 = 0x811c_9dc5;
+const PRIME_32: u32 = 0x0100_0193;
+const OFFSET_BASIS_64: u64 = 0xcbf2_9ce4_8422_2325;
+const PRIME_64: u64 = 0x0100_0000_01b3;
+
+/// 32-bit FNV-1a, `finish` returning the hash zero-extended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fnv1a32(u32);
+
+/// 64-bit FNV-1a.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fnv1a64(u64);
+
+pub type BuildFnv1a32 = BuildHasherDefault<Fnv1a32>;
+
+pub type BuildFnv1a64 = BuildHasherDefault<Fnv1a64>;
+
+impl Fnv1a32 {
+    pub fn new() -> Fnv1a32 {
+        Fnv1a32(OFFSET_BASIS_32)
+    }
+
+    pub fn finish_u32(&self) -> u32 {
+        self.0
+    }
+}
+
+impl Default for Fnv1a32 {
+    fn default() -> Fnv1a32 {
+        Fnv1a32::new()
+    }
+}
+
+impl Hasher for Fnv1a32 {
+    fn finish(&self) -> u64 {
+        u64::from(self.0)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u32::from(byte)).wrapping_mul(PRIME_32);
+        }
+    }
+}
+
+impl Fnv1a64 {
+    pub fn new() -> Fnv1a64 {
+        Fnv1a64(OFFSET_BASIS_64)
+    }
+}
+
+impl Default for Fnv1a64 {
+    fn default() -> Fnv1a64 {
+        Fnv1a64::new()
+    }
+}
+
+impl Hasher for Fnv1a64 {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(PRIME_64);
+        }
+    }
+}
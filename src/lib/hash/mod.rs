@@ -0,0 +1,45 @@
+//! Hash functions implementing `Hasher`, each with a `BuildHasher` for
+//! `HashMap::with_hasher`.
+//!
+//! - [`Fnv1a32`] and [`Fnv1a64`], simple and fast on short keys
+//! - [`SipHasher13`] and [`SipHasher24`], keyed against hash flooding, the
+//!   former being the algorithm of `DefaultHasher`
+//! - [`XxHash64`], fast on long keys
+//!
+//! Unlike `DefaultHasher`, their output is fixed for a given key, and
+//! checked against published test vectors.
+//!
+//! [`StableHash`] hashes values with an encoding independent of the platform,
+//! for digests that can be stored. [`sketch`] has probabilistic data
+//! structures built on hashers, [`analysis`] compares hashers, and [`Ring`]
+//! distributes keys over nodes with consistent hashing.
+//!
+//! # Examples
+//! ```
+//! use lib::hash::{BuildFnv1a64, BuildSipHasher13};
+//! use std::collections::HashMap;
+//!
+//! let mut ages: HashMap<&str, u32, _> = HashMap::with_hasher(BuildFnv1a64::default());
+//! ages.insert("Ada", 36);
+//! assert_eq!(ages["Ada"], 36);
+//!
+//! let mut ages = HashMap::with_hasher(BuildSipHasher13::new(0x0123, 0x4567));
+//! ages.insert("Ada", 36);
+//! assert_eq!(ages["Ada"], 36);
+//! ```
+
+pub mod analysis;
+mod fnv;
+mod ring;
+mod sip;
+pub mod sketch;
+mod stable;
+mod xx;
+
+pub use fnv::{BuildFnv1a32, BuildFnv1a64, Fnv1a32, Fnv1a64};
+pub use ring::{KeyRange, Moved, Ring};
+pub use sip::{
+    BuildSipHasher, BuildSipHasher13, BuildSipHasher24, SipHasher, SipHasher13, SipHasher24,
+};
+pub use stable::{digest, StableHash, VERSION};
+pub use xx::{BuildXxHash64, XxHash64};
@@ -0,0 +1,226 @@
+//! Consistent hashing: keys and nodes are hashed to positions on a ring, a
+//! key belonging to the first node at or after its position.
+//!
+//! Adding or removing a node only moves the keys of the arcs it gains or
+//! loses, instead of almost all keys with `hash % nodes`.
+
+use super::BuildXxHash64;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+
+/// Nodes placed at several positions on the ring, their virtual nodes, so
+/// that each owns many small arcs and receives its share of keys.
+///
+/// A node of weight `w` has `w` times the virtual nodes per weight of the
+/// ring, and receives about `w` times the keys of a node of weight 1.
+///
+/// # Examples
+/// ```
+/// use lib::hash::Ring;
+///
+/// let mut ring = Ring::new(100);
+/// ring.add("cache-a");
+/// ring.add("cache-b");
+/// let moved = ring.add_weighted("cache-c", 2);
+///
+/// let key = "user:42";
+/// let node = ring.get(key).unwrap();
+/// if moved.iter().any(|moved| moved.range.contains(ring.position(key))) {
+///     assert_eq!(*node, "cache-c");
+/// }
+/// let replicas = ring.replicas(key, 2);
+/// assert_eq!(replicas.len(), 2);
+/// assert_ne!(replicas[0], replicas[1]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Ring<N, S = BuildXxHash64> {
+    /// Owners by position
+    points: BTreeMap<u64, N>,
+    /// In insertion order, with their weight
+    nodes: Vec<(N, u32)>,
+    virtual_nodes: u32,
+    build: S,
+}
+
+/// Positions `start` excluded to `end` included, wrapping past `u64::MAX`
+/// when `end <= start`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl KeyRange {
+    pub fn contains(&self, position: u64) -> bool {
+        if self.start < self.end {
+            self.start < position && position <= self.end
+        } else {
+            self.start < position || position <= self.end
+        }
+    }
+}
+
+impl fmt::Display for KeyRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({:#018x}, {:#018x}]", self.start, self.end)
+    }
+}
+
+/// Keys of `range` that moved from a node to another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Moved<N> {
+    pub range: KeyRange,
+    pub from: N,
+    pub to: N,
+}
+
+impl<N: Hash + Eq + Clone> Ring<N> {
+    pub fn new(virtual_nodes: u32) -> Ring<N> {
+        Ring::with_hasher(virtual_nodes, BuildXxHash64::default())
+    }
+}
+
+impl<N: Hash + Eq + Clone, S: BuildHasher> Ring<N, S> {
+    /// With `virtual_nodes` per unit of weight, a hundred or so giving an
+    /// even distribution.
+    pub fn with_hasher(virtual_nodes: u32, build: S) -> Ring<N, S> {
+        Ring {
+            points: BTreeMap::new(),
+            nodes: Vec::new(),
+            virtual_nodes: virtual_nodes.max(1),
+            build,
+        }
+    }
+
+    /// The position of a key on the ring.
+    pub fn position<K: Hash + ?Sized>(&self, key: &K) -> u64 {
+        self.build.hash_one(key)
+    }
+
+    /// Adds a node of weight 1, as [`add_weighted`](Ring::add_weighted).
+    pub fn add(&mut self, node: N) -> Vec<Moved<N>> {
+        self.add_weighted(node, 1)
+    }
+
+    /// Adds a node, or changes its weight if already present, and returns
+    /// the ranges of keys that changed node. Nothing moves when the ring was
+    /// empty, no node owning keys before.
+    pub fn add_weighted(&mut self, node: N, weight: u32) -> Vec<Moved<N>> {
+        let before = self.points.clone();
+        self.detach(&node);
+        if weight > 0 {
+            self.attach(node, weight);
+        }
+        moves(&before, &self.points)
+    }
+
+    /// Removes a node, returning the ranges of keys it owned and their new
+    /// node, none if it was the last one.
+    pub fn remove(&mut self, node: &N) -> Vec<Moved<N>> {
+        let before = self.points.clone();
+        self.detach(node);
+        moves(&before, &self.points)
+    }
+
+    fn attach(&mut self, node: N, weight: u32) {
+        for index in 0..weight.saturating_mul(self.virtual_nodes) {
+            // On the rare collision of two positions, the first node keeps it
+            let position = self.build.hash_one((&node, index));
+            self.points.entry(position).or_insert_with(|| node.clone());
+        }
+        self.nodes.push((node, weight));
+    }
+
+    fn detach(&mut self, node: &N) {
+        self.nodes.retain(|(other, _)| other != node);
+        self.points.retain(|_, other| other != node);
+    }
+
+    /// The node of a key.
+    pub fn get<K: Hash + ?Sized>(&self, key: &K) -> Option<&N> {
+        owner(&self.points, self.position(key))
+    }
+
+    /// Up to `count` distinct nodes for a key, in the order of the ring from
+    /// its position, the first being [`get`](Ring::get).
+    pub fn replicas<K: Hash + ?Sized>(&self, key: &K, count: usize) -> Vec<&N> {
+        let position = self.position(key);
+        let mut replicas: Vec<&N> = Vec::new();
+        let clockwise = self
+            .points
+            .range(position..)
+            .chain(self.points.range(..position));
+        for (_, node) in clockwise {
+            if replicas.len() == count.min(self.nodes.len()) {
+                break;
+            }
+            if !replicas.contains(&node) {
+                replicas.push(node);
+            }
+        }
+        replicas
+    }
+
+    /// The nodes with their weight, in insertion order.
+    pub fn nodes(&self) -> impl Iterator<Item = (&N, u32)> {
+        self.nodes.iter().map(|(node, weight)| (node, *weight))
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+/// The node of the first point at or after `position`, wrapping around.
+fn owner<N>(points: &BTreeMap<u64, N>, position: u64) -> Option<&N> {
+    points
+        .range(position..)
+        .next()
+        .or_else(|| points.iter().next())
+        .map(|(_, node)| node)
+}
+
+/// The arcs whose owner differs between two rings, adjacent arcs with the
+/// same owners being merged.
+fn moves<N: Eq + Clone>(before: &BTreeMap<u64, N>, after: &BTreeMap<u64, N>) -> Vec<Moved<N>> {
+    let mut ends: Vec<u64> = before.keys().chain(after.keys()).copied().collect();
+    ends.sort_unstable();
+    ends.dedup();
+
+    let mut moved: Vec<Moved<N>> = Vec::new();
+    for (index, &end) in ends.iter().enumerate() {
+        // Arcs between consecutive ends have a single owner in both rings
+        let start = ends[(index + ends.len() - 1) % ends.len()];
+        let (Some(from), Some(to)) = (owner(before, end), owner(after, end)) else {
+            continue;
+        };
+        if from == to {
+            continue;
+        }
+        match moved.last_mut() {
+            Some(last) if last.range.end == start && last.from == *from && last.to == *to => {
+                last.range.end = end;
+            }
+            _ => moved.push(Moved {
+                range: KeyRange { start, end },
+                from: from.clone(),
+                to: to.clone(),
+            }),
+        }
+    }
+
+    // The last arc may continue the first one past `u64::MAX`
+    if moved.len() > 1 {
+        let (first, last) = (&moved[0], &moved[moved.len() - 1]);
+        if last.range.end == first.range.start && last.from == first.from && last.to == first.to {
+            let last = moved.pop().unwrap();
+            moved[0].range.start = last.range.start;
+        }
+    }
+    moved
+}
@@ -0,0 +1,136 @@
+//! SipHash-c-d, with `c` rounds per 8-byte block of the message and `d`
+//! rounds at the end, keyed by 128 bits.
+
+use std::hash::{BuildHasher, Hasher};
+
+/// SipHash with `C` compression and `D` finalization rounds.
+///
+/// With a secret random key, an attacker can't find keys that collide in a
+/// `HashMap`. Bytes are buffered until a block of 8 is complete, so writing
+/// a message at once or in pieces gives the same hash.
+#[derive(Debug, Clone, Copy)]
+pub struct SipHasher<const C: usize, const D: usize> {
+    v0: u64,
+    v1: u64,
+    v2: u64,
+    v3: u64,
+    /// The bytes of the incomplete block, little-endian
+    tail: u64,
+    tail_len: usize,
+    len: usize,
+}
+
+pub type SipHasher13 = SipHasher<1, 3>;
+
+pub type SipHasher24 = SipHasher<2, 4>;
+
+impl<const C: usize, const D: usize> SipHasher<C, D> {
+    pub fn new_with_keys(k0: u64, k1: u64) -> SipHasher<C, D> {
+        SipHasher {
+            v0: k0 ^ 0x736f_6d65_7073_6575,
+            v1: k1 ^ 0x646f_7261_6e64_6f6d,
+            v2: k0 ^ 0x6c79_6765_6e65_7261,
+            v3: k1 ^ 0x7465_6462_7974_6573,
+            tail: 0,
+            tail_len: 0,
+            len: 0,
+        }
+    }
+
+    fn compress(&mut self, block: u64) {
+        self.v3 ^= block;
+        self.rounds(C);
+        self.v0 ^= block;
+    }
+
+    fn rounds(&mut self, count: usize) {
+        for _ in 0..count {
+            self.v0 = self.v0.wrapping_add(self.v1);
+            self.v1 = self.v1.rotate_left(13) ^ self.v0;
+            self.v0 = self.v0.rotate_left(32);
+            self.v2 = self.v2.wrapping_add(self.v3);
+            self.v3 = self.v3.rotate_left(16) ^ self.v2;
+            self.v0 = self.v0.wrapping_add(self.v3);
+            self.v3 = self.v3.rotate_left(21) ^ self.v0;
+            self.v2 = self.v2.wrapping_add(self.v1);
+            self.v1 = self.v1.rotate_left(17) ^ self.v2;
+            self.v2 = self.v2.rotate_left(32);
+        }
+    }
+}
+
+/// With keys 0, as `SipHasher13::new` in std.
+impl<const C: usize, const D: usize> Default for SipHasher<C, D> {
+    fn default() -> SipHasher<C, D> {
+        SipHasher::new_with_keys(0, 0)
+    }
+}
+
+impl<const C: usize, const D: usize> Hasher for SipHasher<C, D> {
+    fn finish(&self) -> u64 {
+        let mut state = *self;
+        // The length modulo 256 fills the last byte of the last block
+        let block = ((self.len as u64) << 56) | self.tail;
+        state.compress(block);
+        state.v2 ^= 0xff;
+        state.rounds(D);
+        state.v0 ^ state.v1 ^ state.v2 ^ state.v3
+    }
+
+    fn write(&mut self, mut bytes: &[u8]) {
+        self.len = self.len.wrapping_add(bytes.len());
+
+        if self.tail_len > 0 {
+            let taken = bytes.len().min(8 - self.tail_len);
+            self.tail |= read_le(&bytes[..taken]) << (8 * self.tail_len);
+            self.tail_len += taken;
+            bytes = &bytes[taken..];
+            if self.tail_len < 8 {
+                return;
+            }
+            self.compress(self.tail);
+            self.tail = 0;
+            self.tail_len = 0;
+        }
+
+        let mut blocks = bytes.chunks_exact(8);
+        for block in &mut blocks {
+            self.compress(u64::from_le_bytes(block.try_into().unwrap()));
+        }
+        let rest = blocks.remainder();
+        self.tail = read_le(rest);
+        self.tail_len = rest.len();
+    }
+}
+
+/// Up to 8 bytes as a little-endian integer.
+fn read_le(bytes: &[u8]) -> u64 {
+    let mut buffer = [0; 8];
+    buffer[..bytes.len()].copy_from_slice(bytes);
+    u64::from_le_bytes(buffer)
+}
+
+/// Builds [`SipHasher`]s with the same keys.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BuildSipHasher<const C: usize, const D: usize> {
+    k0: u64,
+    k1: u64,
+}
+
+pub type BuildSipHasher13 = BuildSipHasher<1, 3>;
+
+pub type BuildSipHasher24 = BuildSipHasher<2, 4>;
+
+impl<const C: usize, const D: usize> BuildSipHasher<C, D> {
+    pub fn new(k0: u64, k1: u64) -> BuildSipHasher<C, D> {
+        BuildSipHasher { k0, k1 }
+    }
+}
+
+impl<const C: usize, const D: usize> BuildHasher for BuildSipHasher<C, D> {
+    type Hasher = SipHasher<C, D>;
+
+    fn build_hasher(&self) -> SipHasher<C, D> {
+        SipHasher::new_with_keys(self.k0, self.k1)
+    }
+}
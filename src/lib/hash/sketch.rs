@@ -0,0 +1,659 @@
+//! Probabilistic data structures, answering approximately in a fixed amount
+//! of memory whatever the number of items:
+//!
+//! - [`BloomFilter`]: whether an item was inserted, with false positives
+//! - [`CountingBloomFilter`]: the same, items being removable
+//! - [`CountMinSketch`]: how many times an item was inserted, overestimated
+//! - [`HyperLogLog`]: how many distinct items were inserted
+//!
+//! Items are hashed with the `BuildHasher` of the structure, [`BuildXxHash64`]
+//! by default. Structures built with the same parameters and hasher can be
+//! merged, and serialized to bytes: a deserialized structure must use the
+//! same hasher with the same keys, which excludes `RandomState`.
+//!
+//! # Examples
+//! ```
+//! use lib::hash::sketch::BloomFilter;
+//!
+//! // Skips the events seen before, wrongly about once every 1000 events
+//! let mut seen = BloomFilter::new(10_000, 0.001);
+//! let events = ["login", "click", "login", "logout"];
+//! let fresh: Vec<_> = events.iter().filter(|event| seen.insert(*event)).collect();
+//! assert_eq!(fresh, [&"login", &"click", &"logout"]);
+//! ```
+
+use super::BuildXxHash64;
+use crate::error::Error;
+use std::hash::{BuildHasher, Hash};
+
+/// Merging structures of different parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("cannot merge structures of different parameters")]
+pub struct MergeError;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum DecodeError {
+    #[error("not a serialized {0}")]
+    Kind(&'static str),
+    #[error("expected {expected} bytes, got {actual}")]
+    Length { expected: usize, actual: usize },
+    #[error("invalid parameters")]
+    Parameters,
+}
+
+/// The indexes of `count` hash functions into `len` slots, derived from a
+/// single hash by double hashing.
+fn indexes(hash: u64, count: u32, len: usize) -> impl Iterator<Item = usize> {
+    // Odd, so that the indexes differ when `len` is a power of two
+    let step = hash.rotate_left(32) | 1;
+    (0..u64::from(count))
+        .map(move |index| (hash.wrapping_add(index.wrapping_mul(step)) % len as u64) as usize)
+}
+
+// ____________________________________________________________
+// Bloom filter
+
+/// A set answering `contains` with false positives but no false negatives.
+///
+/// Sized for an expected number of items and a false positive rate, which
+/// grows when more items are inserted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomFilter<S = BuildXxHash64> {
+    bits: Vec<u64>,
+    len: usize,
+    hashes: u32,
+    build: S,
+}
+
+/// The number of bits and of hash functions minimizing the false positive
+/// rate for `items` items.
+fn bloom_parameters(items: usize, false_positive_rate: f64) -> (usize, u32) {
+    assert!(
+        0.0 < false_positive_rate && false_positive_rate < 1.0,
+        "the false positive rate must be in ]0, 1["
+    );
+    let items = items.max(1) as f64;
+    let ln2 = std::f64::consts::LN_2;
+    let bits = (-items * false_positive_rate.ln() / (ln2 * ln2)).ceil();
+    let hashes = (bits / items * ln2).round().max(1.0);
+    (bits as usize, hashes as u32)
+}
+
+impl BloomFilter {
+    pub fn new(items: usize, false_positive_rate: f64) -> BloomFilter {
+        BloomFilter::with_hasher(items, false_positive_rate, BuildXxHash64::default())
+    }
+}
+
+impl<S: BuildHasher> BloomFilter<S> {
+    pub fn with_hasher(items: usize, false_positive_rate: f64, build: S) -> BloomFilter<S> {
+        let (len, hashes) = bloom_parameters(items, false_positive_rate);
+        BloomFilter {
+            bits: vec![0; len.div_ceil(64)],
+            len,
+            hashes,
+            build,
+        }
+    }
+
+    /// Returns whether the item was absent, which is wrong for false
+    /// positives.
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) -> bool {
+        let mut absent = false;
+        for index in indexes(self.build.hash_one(item), self.hashes, self.len) {
+            let (word, bit) = (index / 64, 1 << (index % 64));
+            absent |= self.bits[word] & bit == 0;
+            self.bits[word] |= bit;
+        }
+        absent
+    }
+
+    pub fn contains<T: Hash + ?Sized>(&self, item: &T) -> bool {
+        indexes(self.build.hash_one(item), self.hashes, self.len)
+            .all(|index| self.bits[index / 64] & (1 << (index % 64)) != 0)
+    }
+
+    /// The number of bits.
+    pub fn bits(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|&word| word == 0)
+    }
+
+    pub fn hashes(&self) -> u32 {
+        self.hashes
+    }
+
+    /// The probability of a false positive given the bits set so far.
+    pub fn false_positive_rate(&self) -> f64 {
+        let ones: u32 = self.bits.iter().map(|word| word.count_ones()).sum();
+        (f64::from(ones) / self.len as f64).powi(self.hashes as i32)
+    }
+
+    /// Adds the items of `other`, as if they had been inserted in `self`.
+    pub fn union(&mut self, other: &BloomFilter<S>) -> Result<(), MergeError> {
+        if (self.len, self.hashes) != (other.len, other.hashes) {
+            return Err(MergeError);
+        }
+        for (word, other) in self.bits.iter_mut().zip(&other.bits) {
+            *word |= other;
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Writer::new(BLOOM_FILTER);
+        bytes.u64(self.len as u64);
+        bytes.u32(self.hashes);
+        for word in &self.bits {
+            bytes.u64(*word);
+        }
+        bytes.0
+    }
+
+    pub fn from_bytes(bytes: &[u8], build: S) -> Result<BloomFilter<S>, DecodeError> {
+        let mut reader = Reader::new(bytes, BLOOM_FILTER)?;
+        let len = reader.len()?;
+        let hashes = reader.u32()?;
+        if len == 0 || hashes == 0 {
+            return Err(DecodeError::Parameters);
+        }
+        let bits = reader.u64s(len.div_ceil(64))?;
+        reader.end()?;
+        Ok(BloomFilter {
+            bits,
+            len,
+            hashes,
+            build,
+        })
+    }
+}
+
+// ____________________________________________________________
+// Counting Bloom filter
+
+/// A [`BloomFilter`] of counters instead of bits, so that items can be
+/// removed.
+///
+/// Counters saturate at 255, and are then never decremented. Removing an
+/// item that was not inserted may remove other items.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CountingBloomFilter<S = BuildXxHash64> {
+    counters: Vec<u8>,
+    hashes: u32,
+    build: S,
+}
+
+impl CountingBloomFilter {
+    pub fn new(items: usize, false_positive_rate: f64) -> CountingBloomFilter {
+        CountingBloomFilter::with_hasher(items, false_positive_rate, BuildXxHash64::default())
+    }
+}
+
+impl<S: BuildHasher> CountingBloomFilter<S> {
+    pub fn with_hasher(items: usize, false_positive_rate: f64, build: S) -> CountingBloomFilter<S> {
+        let (len, hashes) = bloom_parameters(items, false_positive_rate);
+        CountingBloomFilter {
+            counters: vec![0; len],
+            hashes,
+            build,
+        }
+    }
+
+    /// Returns whether the item was absent, as [`BloomFilter::insert`].
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) -> bool {
+        let mut absent = false;
+        for index in indexes(self.build.hash_one(item), self.hashes, self.counters.len()) {
+            let counter = &mut self.counters[index];
+            absent |= *counter == 0;
+            *counter = counter.saturating_add(1);
+        }
+        absent
+    }
+
+    /// Removes one insertion of the item, returning whether it was present.
+    pub fn remove<T: Hash + ?Sized>(&mut self, item: &T) -> bool {
+        if !self.contains(item) {
+            return false;
+        }
+        for index in indexes(self.build.hash_one(item), self.hashes, self.counters.len()) {
+            let counter = &mut self.counters[index];
+            if *counter < u8::MAX {
+                *counter -= 1;
+            }
+        }
+        true
+    }
+
+    pub fn contains<T: Hash + ?Sized>(&self, item: &T) -> bool {
+        self.count(item) > 0
+    }
+
+    /// An upper bound of the number of insertions of the item, less its
+    /// removals.
+    pub fn count<T: Hash + ?Sized>(&self, item: &T) -> u8 {
+        indexes(self.build.hash_one(item), self.hashes, self.counters.len())
+            .map(|index| self.counters[index])
+            .min()
+            .unwrap_or(0)
+    }
+
+    pub fn counters(&self) -> usize {
+        self.counters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counters.iter().all(|&counter| counter == 0)
+    }
+
+    pub fn hashes(&self) -> u32 {
+        self.hashes
+    }
+
+    /// Adds the insertions of `other`.
+    pub fn merge(&mut self, other: &CountingBloomFilter<S>) -> Result<(), MergeError> {
+        if (self.counters.len(), self.hashes) != (other.counters.len(), other.hashes) {
+            return Err(MergeError);
+        }
+        for (counter, other) in self.counters.iter_mut().zip(&other.counters) {
+            *counter = counter.saturating_add(*other);
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Writer::new(COUNTING_BLOOM_FILTER);
+        bytes.u64(self.counters.len() as u64);
+        bytes.u32(self.hashes);
+        bytes.0.extend_from_slice(&self.counters);
+        bytes.0
+    }
+
+    pub fn from_bytes(bytes: &[u8], build: S) -> Result<CountingBloomFilter<S>, DecodeError> {
+        let mut reader = Reader::new(bytes, COUNTING_BLOOM_FILTER)?;
+        let len = reader.len()?;
+        let hashes = reader.u32()?;
+        if len == 0 || hashes == 0 {
+            return Err(DecodeError::Parameters);
+        }
+        let counters = reader.take(len)?.to_vec();
+        reader.end()?;
+        Ok(CountingBloomFilter {
+            counters,
+            hashes,
+            build,
+        })
+    }
+}
+
+// ____________________________________________________________
+// Count-Min sketch
+
+/// Counts of items, overestimated by at most `epsilon` times the total
+/// count with a probability of `1 - delta`.
+///
+/// # Examples
+/// ```
+/// use lib::hash::sketch::CountMinSketch;
+///
+/// let mut counts = CountMinSketch::new(0.001, 0.01);
+/// for event in ["login", "click", "click", "click"] {
+///     counts.add(event, 1);
+/// }
+/// assert_eq!(counts.estimate("click"), 3);
+/// assert_eq!(counts.total(), 4);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CountMinSketch<S = BuildXxHash64> {
+    /// `depth` rows of `width` counters
+    counters: Vec<u64>,
+    width: usize,
+    depth: u32,
+    total: u64,
+    build: S,
+}
+
+impl CountMinSketch {
+    pub fn new(epsilon: f64, delta: f64) -> CountMinSketch {
+        CountMinSketch::with_hasher(epsilon, delta, BuildXxHash64::default())
+    }
+}
+
+impl<S: BuildHasher> CountMinSketch<S> {
+    pub fn with_hasher(epsilon: f64, delta: f64, build: S) -> CountMinSketch<S> {
+        assert!(
+            0.0 < epsilon && 0.0 < delta && delta < 1.0,
+            "epsilon must be positive and delta in ]0, 1["
+        );
+        let width = (std::f64::consts::E / epsilon).ceil() as usize;
+        let depth = (1.0 / delta).ln().ceil().max(1.0) as u32;
+        CountMinSketch {
+            counters: vec![0; width * depth as usize],
+            width,
+            depth,
+            total: 0,
+            build,
+        }
+    }
+
+    pub fn add<T: Hash + ?Sized>(&mut self, item: &T, count: u64) {
+        let hash = self.build.hash_one(item);
+        for (row, index) in indexes(hash, self.depth, self.width).enumerate() {
+            let counter = &mut self.counters[row * self.width + index];
+            *counter = counter.saturating_add(count);
+        }
+        self.total = self.total.saturating_add(count);
+    }
+
+    /// At least the count of the item, the minimum of its counters.
+    pub fn estimate<T: Hash + ?Sized>(&self, item: &T) -> u64 {
+        let hash = self.build.hash_one(item);
+        indexes(hash, self.depth, self.width)
+            .enumerate()
+            .map(|(row, index)| self.counters[row * self.width + index])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// The sum of the counts added.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Adds the counts of `other`.
+    pub fn merge(&mut self, other: &CountMinSketch<S>) -> Result<(), MergeError> {
+        if (self.width, self.depth) != (other.width, other.depth) {
+            return Err(MergeError);
+        }
+        for (counter, other) in self.counters.iter_mut().zip(&other.counters) {
+            *counter = counter.saturating_add(*other);
+        }
+        self.total = self.total.saturating_add(other.total);
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Writer::new(COUNT_MIN_SKETCH);
+        bytes.u64(self.width as u64);
+        bytes.u32(self.depth);
+        bytes.u64(self.total);
+        for counter in &self.counters {
+            bytes.u64(*counter);
+        }
+        bytes.0
+    }
+
+    pub fn from_bytes(bytes: &[u8], build: S) -> Result<CountMinSketch<S>, DecodeError> {
+        let mut reader = Reader::new(bytes, COUNT_MIN_SKETCH)?;
+        let width = reader.len()?;
+        let depth = reader.u32()?;
+        let total = reader.u64()?;
+        let len = width
+            .checked_mul(depth as usize)
+            .filter(|&len| len > 0)
+            .ok_or(DecodeError::Parameters)?;
+        let counters = reader.u64s(len)?;
+        reader.end()?;
+        Ok(CountMinSketch {
+            counters,
+            width,
+            depth,
+            total,
+            build,
+        })
+    }
+}
+
+// ____________________________________________________________
+// HyperLogLog
+
+/// An estimate of the number of distinct items inserted, with a standard
+/// error of `1.04 / sqrt(2^precision)`, in `2^precision` bytes.
+///
+/// # Examples
+/// ```
+/// use lib::hash::sketch::HyperLogLog;
+///
+/// // 1.6% of standard error in 4 KiB
+/// let mut users = HyperLogLog::new(12);
+/// for user in (0..100_000).map(|user| user % 20_000) {
+///     users.insert(&user);
+/// }
+/// assert!((users.estimate() - 20_000.0).abs() < 20_000.0 * 0.05);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HyperLogLog<S = BuildXxHash64> {
+    /// The largest rank seen in each register
+    registers: Vec<u8>,
+    precision: u8,
+    build: S,
+}
+
+impl HyperLogLog {
+    pub fn new(precision: u8) -> HyperLogLog {
+        HyperLogLog::with_hasher(precision, BuildXxHash64::default())
+    }
+}
+
+impl<S: BuildHasher> HyperLogLog<S> {
+    pub const PRECISIONS: std::ops::RangeInclusive<u8> = 4..=18;
+
+    pub fn with_hasher(precision: u8, build: S) -> HyperLogLog<S> {
+        assert!(
+            HyperLogLog::<S>::PRECISIONS.contains(&precision),
+            "the precision must be in {:?}",
+            HyperLogLog::<S>::PRECISIONS
+        );
+        HyperLogLog {
+            registers: vec![0; 1 << precision],
+            precision,
+            build,
+        }
+    }
+
+    /// The smallest precision with at most this standard error.
+    pub fn precision_for(standard_error: f64) -> u8 {
+        let registers = (1.04 / standard_error).powi(2);
+        let precision = registers.log2().ceil().max(0.0) as u8;
+        precision.clamp(
+            *HyperLogLog::<S>::PRECISIONS.start(),
+            *HyperLogLog::<S>::PRECISIONS.end(),
+        )
+    }
+
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) {
+        let hash = self.build.hash_one(item);
+        // The first bits select the register, the rank is the position of
+        // the first one in the others, a sentinel bounding it
+        let register = (hash >> (64 - self.precision)) as usize;
+        let rest = (hash << self.precision) | (1 << (self.precision - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        self.registers[register] = self.registers[register].max(rank);
+    }
+
+    pub fn estimate(&self) -> f64 {
+        let registers = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / registers),
+        };
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&rank| 2f64.powi(-i32::from(rank)))
+            .sum();
+        let estimate = alpha * registers * registers / sum;
+
+        // Linear counting is more accurate on small cardinalities
+        let zeros = self.registers.iter().filter(|&&rank| rank == 0).count();
+        if estimate <= 2.5 * registers && zeros > 0 {
+            registers * (registers / zeros as f64).ln()
+        } else {
+            estimate
+        }
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registers.iter().all(|&rank| rank == 0)
+    }
+
+    /// Adds the items of `other`, as if they had been inserted in `self`.
+    pub fn merge(&mut self, other: &HyperLogLog<S>) -> Result<(), MergeError> {
+        if self.precision != other.precision {
+            return Err(MergeError);
+        }
+        for (rank, other) in self.registers.iter_mut().zip(&other.registers) {
+            *rank = (*rank).max(*other);
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Writer::new(HYPER_LOG_LOG);
+        bytes.0.push(self.precision);
+        bytes.0.extend_from_slice(&self.registers);
+        bytes.0
+    }
+
+    pub fn from_bytes(bytes: &[u8], build: S) -> Result<HyperLogLog<S>, DecodeError> {
+        let mut reader = Reader::new(bytes, HYPER_LOG_LOG)?;
+        let precision = reader.take(1)?[0];
+        if !HyperLogLog::<S>::PRECISIONS.contains(&precision) {
+            return Err(DecodeError::Parameters);
+        }
+        let registers = reader.take(1 << precision)?.to_vec();
+        reader.end()?;
+        if registers.iter().any(|&rank| rank > 65 - precision) {
+            return Err(DecodeError::Parameters);
+        }
+        Ok(HyperLogLog {
+            registers,
+            precision,
+            build,
+        })
+    }
+}
+
+// ____________________________________________________________
+// Serialization
+
+// A tag naming the structure and the version of its format, then its
+// parameters and its content in little-endian
+
+struct Kind {
+    tag: [u8; 4],
+    name: &'static str,
+}
+
+const BLOOM_FILTER: Kind = Kind {
+    tag: *b"BLM1",
+    name: "Bloom filter",
+};
+const COUNTING_BLOOM_FILTER: Kind = Kind {
+    tag: *b"CBF1",
+    name: "counting Bloom filter",
+};
+const COUNT_MIN_SKETCH: Kind = Kind {
+    tag: *b"CMS1",
+    name: "Count-Min sketch",
+};
+const HYPER_LOG_LOG: Kind = Kind {
+    tag: *b"HLL1",
+    name: "HyperLogLog",
+};
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn new(kind: Kind) -> Writer {
+        Writer(kind.tag.to_vec())
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    /// The length of the whole input, for errors
+    len: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8], kind: Kind) -> Result<Reader<'a>, DecodeError> {
+        match bytes.split_first_chunk::<4>() {
+            Some((tag, rest)) if *tag == kind.tag => Ok(Reader {
+                bytes: rest,
+                len: bytes.len(),
+            }),
+            _ => Err(DecodeError::Kind(kind.name)),
+        }
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], DecodeError> {
+        if count > self.bytes.len() {
+            // Decoded lengths are not trusted
+            let expected = (self.len - self.bytes.len())
+                .checked_add(count)
+                .ok_or(DecodeError::Parameters)?;
+            return Err(DecodeError::Length {
+                expected,
+                actual: self.len,
+            });
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn len(&mut self) -> Result<usize, DecodeError> {
+        usize::try_from(self.u64()?).map_err(|_| DecodeError::Parameters)
+    }
+
+    fn u64s(&mut self, count: usize) -> Result<Vec<u64>, DecodeError> {
+        let bytes = self.take(count.checked_mul(8).ok_or(DecodeError::Parameters)?)?;
+        Ok(bytes
+            .chunks_exact(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+            .collect())
+    }
+
+    fn end(&self) -> Result<(), DecodeError> {
+        match self.bytes.len() {
+            0 => Ok(()),
+            extra => Err(DecodeError::Length {
+                expected: self.len - extra,
+                actual: self.len,
+            }),
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! Hashing with an encoding fixed across platforms and Rust releases, for
+//! hashes that are stored or sent.
+//!
+//! `Hash` implementations of std may change, and write integers in native
+//! endianness. [`StableHash`] writes every value as bytes that only depend
+//! on the value:
+//! - integers in little-endian, `usize` and `isize` as 64 bits
+//! - `bool` as a byte, `char` as a `u32`, floats as their bits
+//! - strings, slices, arrays and collections prefixed by their length
+//! - `Option` and `Result` prefixed by a tag byte
+//! - tuples and structs as their fields in order, enums prefixed by the
+//!   index of the variant as a `u32`
+//! - maps and sets in the order of their keys, so that a `HashMap` hashes
+//!   as the `BTreeMap` with the same entries
+
+use super::SipHasher24;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::hash::Hasher;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// The version of the encoding, hashed first by [`digest`] so that digests
+/// of another version don't match by accident.
+pub const VERSION: u32 = 1;
+
+/// `Hash` with an encoding independent of the platform, see the
+/// [module](self).
+///
+/// Derived implementations hash the fields in declaration order, not their
+/// names: reordering fields changes digests, renaming them does not.
+///
+/// # Examples
+/// ```
+/// use lib::hash::{self, StableHash};
+///
+/// #[derive(StableHash)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// assert_eq!(hash::digest(&Point { x: 1, y: 2 }), hash::digest(&(1, 2)));
+/// assert_ne!(hash::digest(&Point { x: 1, y: 2 }), hash::digest(&(2, 1)));
+/// ```
+pub trait StableHash {
+    /// Writes the encoding of `self` with `Hasher::write` only, the other
+    /// methods of `Hasher` using the native endianness.
+    fn stable_hash<H: Hasher>(&self, state: &mut H);
+}
+
+pub use local_macro::StableHash;
+
+/// The stable hash of `value` as SipHash-2-4 with keys 0, preceded by the
+/// [`VERSION`] of the encoding.
+pub fn digest<T: StableHash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = SipHasher24::new_with_keys(0, 0);
+    VERSION.stable_hash(&mut hasher);
+    value.stable_hash(&mut hasher);
+    hasher.finish()
+}
+
+fn length<H: Hasher>(length: usize, state: &mut H) {
+    length.stable_hash(state);
+}
+
+// ____________________________________________________________
+// Primitives
+
+macro_rules! impl_integers {
+    ($($ty:ty),*) => {
+        $(
+            impl StableHash for $ty {
+                fn stable_hash<H: Hasher>(&self, state: &mut H) {
+                    state.write(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_integers!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl StableHash for usize {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        (*self as u64).stable_hash(state);
+    }
+}
+
+impl StableHash for isize {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        (*self as i64).stable_hash(state);
+    }
+}
+
+impl StableHash for bool {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        u8::from(*self).stable_hash(state);
+    }
+}
+
+impl StableHash for char {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        u32::from(*self).stable_hash(state);
+    }
+}
+
+/// The bits, so `0.0` and `-0.0` differ, as NaNs of different payloads.
+impl StableHash for f32 {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        self.to_bits().stable_hash(state);
+    }
+}
+
+impl StableHash for f64 {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        self.to_bits().stable_hash(state);
+    }
+}
+
+impl StableHash for str {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        length(self.len(), state);
+        state.write(self.as_bytes());
+    }
+}
+
+impl StableHash for String {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().stable_hash(state);
+    }
+}
+
+// ____________________________________________________________
+// Pointers, tuples, Option and Result
+
+macro_rules! impl_pointers {
+    ($($pointer:ty),*) => {
+        $(
+            impl<T: StableHash + ?Sized> StableHash for $pointer {
+                fn stable_hash<H: Hasher>(&self, state: &mut H) {
+                    (**self).stable_hash(state);
+                }
+            }
+        )*
+    };
+}
+
+impl_pointers!(&T, &mut T, Box<T>, Rc<T>, Arc<T>);
+
+macro_rules! impl_tuples {
+    ($(($($name:ident),*),)*) => {
+        $(
+            impl<$($name: StableHash),*> StableHash for ($($name,)*) {
+                #[allow(non_snake_case, unused_variables)]
+                fn stable_hash<H: Hasher>(&self, state: &mut H) {
+                    let ($($name,)*) = self;
+                    $($name.stable_hash(state);)*
+                }
+            }
+        )*
+    };
+}
+
+impl_tuples! {
+    (),
+    (A),
+    (A, B),
+    (A, B, C),
+    (A, B, C, D),
+    (A, B, C, D, E),
+    (A, B, C, D, E, F),
+    (A, B, C, D, E, F, G),
+    (A, B, C, D, E, F, G, I),
+}
+
+impl<T: StableHash> StableHash for Option<T> {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            None => 0u8.stable_hash(state),
+            Some(value) => {
+                1u8.stable_hash(state);
+                value.stable_hash(state);
+            }
+        }
+    }
+}
+
+impl<T: StableHash, E: StableHash> StableHash for Result<T, E> {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Ok(value) => {
+                0u8.stable_hash(state);
+                value.stable_hash(state);
+            }
+            Err(error) => {
+                1u8.stable_hash(state);
+                error.stable_hash(state);
+            }
+        }
+    }
+}
+
+// ____________________________________________________________
+// Sequences and collections
+
+fn sequence<'a, T: StableHash + 'a, H: Hasher>(
+    items: impl ExactSizeIterator<Item = &'a T>,
+    state: &mut H,
+) {
+    length(items.len(), state);
+    for item in items {
+        item.stable_hash(state);
+    }
+}
+
+/// As a slice, a `[T; N]`, a `Vec<T>` and a `VecDeque<T>` hashing the same.
+impl<T: StableHash> StableHash for [T] {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        sequence(self.iter(), state);
+    }
+}
+
+impl<T: StableHash, const N: usize> StableHash for [T; N] {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        self.as_slice().stable_hash(state);
+    }
+}
+
+impl<T: StableHash> StableHash for Vec<T> {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        self.as_slice().stable_hash(state);
+    }
+}
+
+impl<T: StableHash> StableHash for VecDeque<T> {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        sequence(self.iter(), state);
+    }
+}
+
+impl<T: StableHash> StableHash for BTreeSet<T> {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        sequence(self.iter(), state);
+    }
+}
+
+impl<K: StableHash, V: StableHash> StableHash for BTreeMap<K, V> {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        length(self.len(), state);
+        for entry in self {
+            entry.stable_hash(state);
+        }
+    }
+}
+
+/// Sorted, to hash as a `BTreeSet`.
+impl<T: StableHash + Ord, S> StableHash for HashSet<T, S> {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        let mut items: Vec<&T> = self.iter().collect();
+        items.sort_unstable();
+        items.stable_hash(state);
+    }
+}
+
+/// Sorted by key, to hash as a `BTreeMap`.
+impl<K: StableHash + Ord, V: StableHash, S> StableHash for HashMap<K, V, S> {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        let mut entries: Vec<(&K, &V)> = self.iter().collect();
+        entries.sort_unstable_by_key(|&(key, _)| key);
+        entries.stable_hash(state);
+    }
+}
@@ -0,0 +1,160 @@
+//! xxHash64, processing 32 bytes at a time in 4 independent lanes.
+
+use std::hash::{BuildHasher, Hasher};
+
+const PRIME_1: u64 = 0x9e37_79b1_85eb_ca87;
+const PRIME_2: u64 = 0xc2b2_ae3d_27d4_eb4f;
+const PRIME_3: u64 = 0x1656_67b1_9e37_79f9;
+const PRIME_4: u64 = 0x85eb_ca77_c2b2_ae63;
+const PRIME_5: u64 = 0x27d4_eb2f_1656_67c5;
+
+const STRIPE: usize = 32;
+
+/// The 64-bit xxHash, seeded.
+///
+/// Not keyed against hash flooding as [`SipHasher13`](super::SipHasher13),
+/// but several times faster on long keys.
+#[derive(Debug, Clone, Copy)]
+pub struct XxHash64 {
+    seed: u64,
+    lanes: [u64; 4],
+    /// The bytes of the incomplete stripe
+    buffer: [u8; STRIPE],
+    buffer_len: usize,
+    len: u64,
+}
+
+impl XxHash64 {
+    pub fn with_seed(seed: u64) -> XxHash64 {
+        XxHash64 {
+            seed,
+            lanes: [
+                seed.wrapping_add(PRIME_1).wrapping_add(PRIME_2),
+                seed.wrapping_add(PRIME_2),
+                seed,
+                seed.wrapping_sub(PRIME_1),
+            ],
+            buffer: [0; STRIPE],
+            buffer_len: 0,
+            len: 0,
+        }
+    }
+
+    fn stripe(&mut self, stripe: &[u8]) {
+        for (lane, word) in self.lanes.iter_mut().zip(stripe.chunks_exact(8)) {
+            *lane = round(*lane, u64::from_le_bytes(word.try_into().unwrap()));
+        }
+    }
+}
+
+fn round(accumulator: u64, input: u64) -> u64 {
+    accumulator
+        .wrapping_add(input.wrapping_mul(PRIME_2))
+        .rotate_left(31)
+        .wrapping_mul(PRIME_1)
+}
+
+fn merge(accumulator: u64, lane: u64) -> u64 {
+    (accumulator ^ round(0, lane))
+        .wrapping_mul(PRIME_1)
+        .wrapping_add(PRIME_4)
+}
+
+impl Default for XxHash64 {
+    fn default() -> XxHash64 {
+        XxHash64::with_seed(0)
+    }
+}
+
+impl Hasher for XxHash64 {
+    fn finish(&self) -> u64 {
+        let mut hash = if self.len >= STRIPE as u64 {
+            let [v1, v2, v3, v4] = self.lanes;
+            let hash = v1
+                .rotate_left(1)
+                .wrapping_add(v2.rotate_left(7))
+                .wrapping_add(v3.rotate_left(12))
+                .wrapping_add(v4.rotate_left(18));
+            self.lanes
+                .iter()
+                .fold(hash, |hash, &lane| merge(hash, lane))
+        } else {
+            self.seed.wrapping_add(PRIME_5)
+        };
+        hash = hash.wrapping_add(self.len);
+
+        let mut rest = &self.buffer[..self.buffer_len];
+        while let Some((word, tail)) = rest.split_first_chunk::<8>() {
+            hash ^= round(0, u64::from_le_bytes(*word));
+            hash = hash
+                .rotate_left(27)
+                .wrapping_mul(PRIME_1)
+                .wrapping_add(PRIME_4);
+            rest = tail;
+        }
+        if let Some((word, tail)) = rest.split_first_chunk::<4>() {
+            hash ^= u64::from(u32::from_le_bytes(*word)).wrapping_mul(PRIME_1);
+            hash = hash
+                .rotate_left(23)
+                .wrapping_mul(PRIME_2)
+                .wrapping_add(PRIME_3);
+            rest = tail;
+        }
+        for &byte in rest {
+            hash ^= u64::from(byte).wrapping_mul(PRIME_5);
+            hash = hash.rotate_left(11).wrapping_mul(PRIME_1);
+        }
+
+        // Avalanche
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(PRIME_2);
+        hash ^= hash >> 29;
+        hash = hash.wrapping_mul(PRIME_3);
+        hash ^ (hash >> 32)
+    }
+
+    fn write(&mut self, mut bytes: &[u8]) {
+        self.len = self.len.wrapping_add(bytes.len() as u64);
+
+        if self.buffer_len > 0 {
+            let taken = bytes.len().min(STRIPE - self.buffer_len);
+            self.buffer[self.buffer_len..self.buffer_len + taken].copy_from_slice(&bytes[..taken]);
+            self.buffer_len += taken;
+            bytes = &bytes[taken..];
+            if self.buffer_len < STRIPE {
+                return;
+            }
+            let buffer = self.buffer;
+            self.stripe(&buffer);
+            self.buffer_len = 0;
+        }
+
+        let mut stripes = bytes.chunks_exact(STRIPE);
+        for stripe in &mut stripes {
+            self.stripe(stripe);
+        }
+        let rest = stripes.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffer_len = rest.len();
+    }
+}
+
+/// Builds [`XxHash64`]s with the same seed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BuildXxHash64 {
+    seed: u64,
+}
+
+impl BuildXxHash64 {
+    pub fn with_seed(seed: u64) -> BuildXxHash64 {
+        BuildXxHash64 { seed }
+    }
+}
+
+impl BuildHasher for BuildXxHash64 {
+    type Hasher = XxHash64;
+
+    fn build_hasher(&self) -> XxHash64 {
+        XxHash64::with_seed(self.seed)
+    }
+}
@@ -0,0 +1,287 @@
+//! Byte diagrams of the memory layout of types, and comparison of the layout
+//! of a Rust type with the one of its C counterpart.
+
+use crate::describe::{Describe, TypeDescription, TypeKind};
+use std::fmt;
+use std::ops::Range;
+
+/// The fields of a type, at their offsets.
+///
+/// Displayed as a diagram with one letter per byte of a field, in uppercase
+/// for the bytes holding a niche, `.` for padding, `t` for the tag of enums
+/// and `?` for their other bytes, whose variant fields are laid out by the
+/// compiler. Bytes are grouped by the alignment of the type.
+///
+/// Niche bytes are located in the scalars with invalid values, as `bool`,
+/// `char`, `NonZero` integers and references, in the tag of enums and in the
+/// fields of types implementing [`Describe`]. The niches of other types are
+/// only mentioned.
+///
+/// # Examples
+/// ```
+/// use lib::describe::Describe;
+/// use lib::layout::Layout;
+///
+/// #[derive(Describe)]
+/// #[repr(C)]
+/// struct Value {
+///     integer: i32,
+///     boolean: bool,
+/// }
+///
+/// let layout = Layout::of::<Value>();
+/// assert_eq!(layout.padding(), vec![(5..8)]);
+/// assert_eq!(
+///     layout.to_string().lines().nth(1).unwrap(),
+///     "     0 | a a a a | B . . . |"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub name: &'static str,
+    pub size: usize,
+    pub align: usize,
+    /// By offset, which is not the declaration order without `repr(C)`
+    pub fields: Vec<FieldLayout>,
+    /// Whether the fields of the type are unknown, as for enums
+    pub opaque: bool,
+    /// The bytes of the tag of enums, if known
+    pub tag: Option<Range<usize>>,
+    pub has_niche: bool,
+    /// The bytes known to hold the niche, maybe none when it has one
+    pub niche: Vec<Range<usize>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldLayout {
+    pub name: &'static str,
+    pub type_name: &'static str,
+    pub offset: usize,
+    pub size: usize,
+    pub has_niche: bool,
+    /// As [`Layout::niche`], at offsets in the type
+    pub niche: Vec<Range<usize>>,
+}
+
+impl Layout {
+    pub fn of<T: Describe>() -> Layout {
+        Layout::from_description(&T::describe())
+    }
+
+    pub fn from_description(description: &TypeDescription) -> Layout {
+        let mut fields: Vec<_> = description
+            .fields
+            .iter()
+            .filter_map(|field| {
+                let offset = field.offset?;
+                Some(FieldLayout {
+                    name: field.name,
+                    type_name: field.type_name,
+                    offset,
+                    size: field.size,
+                    has_niche: field.niche,
+                    niche: field
+                        .niche_bytes
+                        .iter()
+                        .map(|range| offset + range.start..offset + range.end)
+                        .collect(),
+                })
+            })
+            .collect();
+        fields.sort_by_key(|field| field.offset);
+        Layout {
+            name: description.name,
+            size: description.size,
+            align: description.align,
+            fields,
+            opaque: description.kind == TypeKind::Enum,
+            tag: description.tag.clone(),
+            has_niche: description.niche,
+            niche: description.niche_bytes(),
+        }
+    }
+
+    /// The byte ranges not covered by any field.
+    pub fn padding(&self) -> Vec<Range<usize>> {
+        if self.opaque {
+            return Vec::new();
+        }
+        let mut covered = vec![false; self.size];
+        for field in &self.fields {
+            for byte in &mut covered[field.offset..field.offset + field.size] {
+                *byte = true;
+            }
+        }
+        let mut padding: Vec<Range<usize>> = Vec::new();
+        for (offset, _) in covered.iter().enumerate().filter(|(_, &byte)| !byte) {
+            match padding.last_mut() {
+                Some(range) if range.end == offset => range.end += 1,
+                _ => padding.push(offset..offset + 1),
+            }
+        }
+        padding
+    }
+
+    /// The differences with `other`, fields being matched by position as
+    /// they may be named differently.
+    pub fn compare(&self, other: &Layout) -> Vec<Mismatch> {
+        let mut mismatches = Vec::new();
+        if self.size != other.size {
+            mismatches.push(Mismatch::Size(self.size, other.size));
+        }
+        if self.align != other.align {
+            mismatches.push(Mismatch::Align(self.align, other.align));
+        }
+        if self.fields.len() != other.fields.len() {
+            mismatches.push(Mismatch::FieldCount(self.fields.len(), other.fields.len()));
+        }
+        for (field, other_field) in self.fields.iter().zip(&other.fields) {
+            if field.offset != other_field.offset {
+                mismatches.push(Mismatch::Offset {
+                    field: field.name,
+                    offsets: (field.offset, other_field.offset),
+                });
+            }
+            if field.size != other_field.size {
+                mismatches.push(Mismatch::FieldSize {
+                    field: field.name,
+                    sizes: (field.size, other_field.size),
+                });
+            }
+        }
+        mismatches
+    }
+
+    fn symbol(&self, offset: usize) -> char {
+        let niche = self.niche.iter().any(|range| range.contains(&offset));
+        if self.opaque {
+            return match &self.tag {
+                Some(tag) if tag.contains(&offset) && niche => 'T',
+                Some(tag) if tag.contains(&offset) => 't',
+                _ => '?',
+            };
+        }
+        let Some(index) = self
+            .fields
+            .iter()
+            .position(|field| (field.offset..field.offset + field.size).contains(&offset))
+        else {
+            return '.';
+        };
+        field_symbol(index, niche)
+    }
+}
+
+fn field_symbol(index: usize, niche: bool) -> char {
+    match u8::try_from(index) {
+        Ok(index @ 0..26) if niche => char::from(b'A' + index),
+        Ok(index @ 0..26) => char::from(b'a' + index),
+        _ => '#',
+    }
+}
+
+/// The mention of a niche, with its bytes if they are known.
+fn niche(has_niche: bool, bytes: &[Range<usize>]) -> String {
+    match bytes {
+        _ if !has_niche => String::new(),
+        [] => ", has a niche in unknown bytes".to_string(),
+        _ => {
+            let bytes: Vec<_> = bytes.iter().map(|range| format!("{range:?}")).collect();
+            format!(", has a niche in bytes {}", bytes.join(", "))
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: size {}, align {}{}",
+            self.name,
+            self.size,
+            self.align,
+            niche(self.has_niche, &self.niche)
+        )?;
+
+        let group = self.align.max(1);
+        let row = group.max(8);
+        for start in (0..self.size).step_by(row) {
+            write!(f, "\n{start:>6} |")?;
+            for offset in start..(start + row).min(self.size) {
+                write!(f, " {}", self.symbol(offset))?;
+                if (offset + 1) % group == 0 || offset + 1 == self.size {
+                    write!(f, " |")?;
+                }
+            }
+        }
+
+        for (index, field) in self.fields.iter().enumerate() {
+            write!(
+                f,
+                "\n{:>6}  {}: {}, offset {}, size {}{}",
+                field_symbol(index, false),
+                field.name,
+                field.type_name,
+                field.offset,
+                field.size,
+                niche(field.has_niche, &field.niche)
+            )?;
+        }
+        let padding: usize = self.padding().iter().map(ExactSizeIterator::len).sum();
+        if padding > 0 {
+            write!(f, "\n{:>6}  padding, {padding} bytes", '.')?;
+        }
+        if let Some(tag) = self.tag.as_ref().filter(|tag| !tag.is_empty()) {
+            write!(
+                f,
+                "\n{:>6}  tag, offset {}, size {}",
+                't',
+                tag.start,
+                tag.len()
+            )?;
+        }
+        match &self.tag {
+            Some(tag) if self.opaque && self.size > tag.len() => {
+                write!(f, "\n{:>6}  variant fields", '?')?;
+            }
+            None if self.opaque && self.size > 0 => {
+                write!(f, "\n{:>6}  tag and variant fields", '?')?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// A difference between two layouts, the values being the ones of the
+/// compared layout and of the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mismatch {
+    Size(usize, usize),
+    Align(usize, usize),
+    FieldCount(usize, usize),
+    Offset {
+        field: &'static str,
+        offsets: (usize, usize),
+    },
+    FieldSize {
+        field: &'static str,
+        sizes: (usize, usize),
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Size(a, b) => write!(f, "size {a} != {b}"),
+            Mismatch::Align(a, b) => write!(f, "align {a} != {b}"),
+            Mismatch::FieldCount(a, b) => write!(f, "{a} fields != {b}"),
+            Mismatch::Offset { field, offsets } => {
+                write!(f, "offset of `{field}` {} != {}", offsets.0, offsets.1)
+            }
+            Mismatch::FieldSize { field, sizes } => {
+                write!(f, "size of `{field}` {} != {}", sizes.0, sizes.1)
+            }
+        }
+    }
+}
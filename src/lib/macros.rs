@@ -0,0 +1,239 @@
+//! Declarative macros, exported at the crate root too.
+//!
+//! Production versions of the macros in `tests/r_14_macro.rs`.
+
+use std::ops::Add;
+
+/// The smallest type that can hold every value of `Self` and `Rhs`.
+///
+/// Used by [`min!`], [`max!`] and [`sum!`] to combine arguments of different
+/// numeric types without losing precision.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` and `{Rhs}` have no common type holding both without loss",
+    label = "cannot be combined with `{Rhs}`",
+    note = "convert one of the arguments explicitly, for example with `try_into()`"
+)]
+pub trait Promote<Rhs>: Sized {
+    type Output: From<Self> + From<Rhs>;
+}
+
+macro_rules! impl_promote {
+    ($($lhs:ty: [$($rhs:ty => $output:ty),*],)*) => {
+        $($(
+            impl Promote<$rhs> for $lhs {
+                type Output = $output;
+            }
+        )*)*
+    };
+}
+
+impl_promote! {
+    u8: [u8 => u8, i8 => i16, u16 => u16, i16 => i16, u32 => u32, i32 => i32, u64 => u64, i64 => i64, u128 => u128, i128 => i128, usize => usize, isize => isize, f32 => f32, f64 => f64],
+    i8: [u8 => i16, i8 => i8, u16 => i32, i16 => i16, u32 => i64, i32 => i32, u64 => i128, i64 => i64, i128 => i128, isize => isize, f32 => f32, f64 => f64],
+    u16: [u8 => u16, i8 => i32, u16 => u16, i16 => i32, u32 => u32, i32 => i32, u64 => u64, i64 => i64, u128 => u128, i128 => i128, usize => usize, f32 => f32, f64 => f64],
+    i16: [u8 => i16, i8 => i16, u16 => i32, i16 => i16, u32 => i64, i32 => i32, u64 => i128, i64 => i64, i128 => i128, isize => isize, f32 => f32, f64 => f64],
+    u32: [u8 => u32, i8 => i64, u16 => u32, i16 => i64, u32 => u32, i32 => i64, u64 => u64, i64 => i64, u128 => u128, i128 => i128, f32 => f64, f64 => f64],
+    i32: [u8 => i32, i8 => i32, u16 => i32, i16 => i32, u32 => i64, i32 => i32, u64 => i128, i64 => i64, i128 => i128, f32 => f64, f64 => f64],
+    u64: [u8 => u64, i8 => i128, u16 => u64, i16 => i128, u32 => u64, i32 => i128, u64 => u64, i64 => i128, u128 => u128, i128 => i128],
+    i64: [u8 => i64, i8 => i64, u16 => i64, i16 => i64, u32 => i64, i32 => i64, u64 => i128, i64 => i64, i128 => i128],
+    u128: [u8 => u128, u16 => u128, u32 => u128, u64 => u128, u128 => u128],
+    i128: [u8 => i128, i8 => i128, u16 => i128, i16 => i128, u32 => i128, i32 => i128, u64 => i128, i64 => i128, i128 => i128],
+    usize: [u8 => usize, u16 => usize, usize => usize],
+    isize: [u8 => isize, i8 => isize, i16 => isize, isize => isize],
+    f32: [u8 => f32, i8 => f32, u16 => f32, i16 => f32, u32 => f64, i32 => f64, f32 => f32, f64 => f64],
+    f64: [u8 => f64, i8 => f64, u16 => f64, i16 => f64, u32 => f64, i32 => f64, f32 => f64, f64 => f64],
+}
+
+#[doc(hidden)]
+pub fn min2<A: Promote<B>, B>(a: A, b: B) -> A::Output
+where
+    A::Output: PartialOrd,
+{
+    let (a, b) = (A::Output::from(a), A::Output::from(b));
+    if b < a {
+        b
+    } else {
+        a
+    }
+}
+
+#[doc(hidden)]
+pub fn max2<A: Promote<B>, B>(a: A, b: B) -> A::Output
+where
+    A::Output: PartialOrd,
+{
+    let (a, b) = (A::Output::from(a), A::Output::from(b));
+    if b > a {
+        b
+    } else {
+        a
+    }
+}
+
+#[doc(hidden)]
+pub fn add2<A: Promote<B>, B>(a: A, b: B) -> A::Output
+where
+    A::Output: Add<Output = A::Output>,
+{
+    A::Output::from(a) + A::Output::from(b)
+}
+
+/// The smallest argument, in the smallest type holding all of them.
+///
+/// # Examples
+/// ```
+/// let x: i16 = lib::min!(5u8, -2i8, 3u8);
+/// assert_eq!(x, -2);
+/// assert_eq!(lib::min!(2.5f32, 1u16), 1.0);
+/// ```
+///
+/// Arguments without a lossless common type are rejected:
+/// ```compile_fail,E0277
+/// lib::min!(1u64, 1.0f64);
+/// ```
+#[macro_export]
+macro_rules! min {
+    () => {
+        ::std::compile_error!("`min!` takes at least one argument")
+    };
+    ($x:expr $(,)?) => {
+        $x
+    };
+    ($x:expr, $($rest:expr),+ $(,)?) => {
+        $crate::macros::min2($x, $crate::min!($($rest),+))
+    };
+}
+
+/// The largest argument, in the smallest type holding all of them.
+///
+/// # Examples
+/// ```
+/// let x: i64 = lib::max!(5u32, -2i32);
+/// assert_eq!(x, 5);
+/// ```
+///
+/// ```compile_fail,E0277
+/// lib::max!(1usize, 1u32);
+/// ```
+#[macro_export]
+macro_rules! max {
+    () => {
+        ::std::compile_error!("`max!` takes at least one argument")
+    };
+    ($x:expr $(,)?) => {
+        $x
+    };
+    ($x:expr, $($rest:expr),+ $(,)?) => {
+        $crate::macros::max2($x, $crate::max!($($rest),+))
+    };
+}
+
+/// The sum of the arguments, in the smallest type holding all of them.
+///
+/// # Examples
+/// ```
+/// assert_eq!(lib::sum!(250u8, 10u16, 1i8), 261i32);
+/// ```
+///
+/// ```compile_fail,E0277
+/// lib::sum!(1i128, 1.0f32);
+/// ```
+#[macro_export]
+macro_rules! sum {
+    () => {
+        ::std::compile_error!("`sum!` takes at least one argument")
+    };
+    ($x:expr $(,)?) => {
+        $x
+    };
+    ($x:expr, $($rest:expr),+ $(,)?) => {
+        $crate::macros::add2($x, $crate::sum!($($rest),+))
+    };
+}
+
+/// Evaluates each `eval` expression, returning one value or a tuple.
+///
+/// # Examples
+/// ```
+/// assert_eq!(lib::calculate!(eval 1 + 2), 3);
+/// assert_eq!(lib::calculate!(eval 1 + 2, eval (2 * 3) + 1), (3, 7));
+/// ```
+#[macro_export]
+macro_rules! calculate {
+    () => {
+        ::std::compile_error!("`calculate!` takes at least one `eval` expression")
+    };
+    (eval $e:expr $(,)?) => {
+        $e
+    };
+    ($(eval $e:expr),+ $(,)?) => {
+        ($($e),+)
+    };
+}
+
+/// Generates functions.
+///
+/// `create_function!(name)` generates a `fn name() -> &'static str` returning
+/// its own name. A table generates one function per row, all sharing the
+/// signature written before it.
+///
+/// # Examples
+/// ```
+/// lib::create_function!(foo);
+/// assert_eq!(foo(), "foo");
+///
+/// lib::create_function! {
+///     pub fn(x: i32, y: i32) -> i32 {
+///         /// Sum of the arguments
+///         add => x + y,
+///         sub => x - y,
+///         hypot2 => x * x + y * y,
+///     }
+/// }
+/// assert_eq!(add(3, 4), 7);
+/// assert_eq!(sub(3, 4), -1);
+/// assert_eq!(hypot2(3, 4), 25);
+/// ```
+///
+/// Rows must have distinct names:
+/// ```compile_fail,E0428
+/// lib::create_function! {
+///     fn(x: i32) -> i32 {
+///         same => x,
+///         same => -x,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! create_function {
+    (
+        $vis:vis fn $params:tt -> $output:ty {
+            $($(#[$meta:meta])* $name:ident => $body:expr),+ $(,)?
+        }
+    ) => {
+        $(
+            $(#[$meta])*
+            $vis fn $name $params -> $output {
+                $body
+            }
+        )+
+    };
+    (
+        $vis:vis fn $params:tt {
+            $($(#[$meta:meta])* $name:ident => $body:expr),+ $(,)?
+        }
+    ) => {
+        $crate::create_function! {
+            $vis fn $params -> () {
+                $($(#[$meta])* $name => $body),+
+            }
+        }
+    };
+    ($vis:vis $name:ident) => {
+        $vis fn $name() -> &'static str {
+            ::std::stringify!($name)
+        }
+    };
+}
+
+pub use crate::{calculate, create_function, max, min, sum};
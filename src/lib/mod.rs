@@ -1,21 +1,64 @@
+// Lets the code generated by `local-macro` name this crate as `::lib`
+extern crate self as lib;
+
+pub mod cancel;
+pub mod chan;
+pub mod clib;
+pub mod describe;
+pub mod error;
+pub mod exit;
+pub mod hash;
 mod incrementer;
+pub mod layout;
+pub mod macros;
+pub mod par;
+pub mod pool;
+pub mod quantity;
+pub mod registry;
+pub mod report;
+pub mod state_machine;
+pub mod sync;
+pub mod trace;
+pub mod type_map;
+pub use describe::Describe;
+pub use hash::StableHash;
 pub use incrementer::increment;
+use local_crate::expr;
+use std::error::Error;
 use std::io;
 use std::io::Write;
+pub use type_map::TypeMap;
+
+/// Evaluates an integer expression at compile time.
+///
+/// The grammar is the one of `local_crate::expr`, also accepted by [`run`].
+/// Overflows and divisions by zero are compile errors pointing at the
+/// operator, the expansion is an unsuffixed literal.
+///
+/// # Examples
+/// ```
+/// const SECONDS_PER_DAY: u32 = lib::const_eval!(24 * 60 * 60);
+/// assert_eq!(SECONDS_PER_DAY, 86_400);
+/// assert_eq!(lib::const_eval!(-(7 - 10) % 2), 1);
+/// ```
+///
+/// ```compile_fail
+/// let x = lib::const_eval!(1 / (2 - 2));
+/// ```
+///
+/// ```compile_fail
+/// let x = lib::const_eval!(9_223_372_036_854_775_807 + 1);
+/// ```
+pub use local_macro::const_eval;
 
-pub fn run() {
+pub fn run() -> Result<(), Box<dyn Error>> {
     print!("Insert a number: ");
-    io::stdout().flush().unwrap();
-    let mut value = String::new();
+    io::stdout().flush()?;
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
 
-    if let Some((value, value_plus_1)) = io::stdin()
-        .read_line(&mut value)
-        .ok()
-        .and_then(|_| value.trim().parse().ok())
-        .map(|value| (value, incrementer::increment(value)))
-    {
-        println!("{value} + 1 = {value_plus_1}");
-    } else {
-        println!("Failed to read a number");
-    }
+    // Accepts an expression, as `const_eval!`
+    let value = i32::try_from(expr::evaluate(line.trim())?)?;
+    println!("{value} + 1 = {}", incrementer::increment(value));
+    Ok(())
 }
@@ -0,0 +1,183 @@
+//! Data-parallel operations on slices, run on scoped threads.
+//!
+//! A slice is cut into chunks, several per thread, that the threads take in
+//! turn: a thread slowed down by costly items takes fewer chunks. Results are
+//! in the order of the items whatever the thread that computed them, and a
+//! panic in a thread stops the others and is resumed in the caller.
+//!
+//! The number of threads is the available parallelism, unless set for the
+//! process by [`set_threads`] or for a closure by [`with_threads`].
+//!
+//! # Examples
+//! ```
+//! use lib::par::ParallelSlice;
+//!
+//! let numbers: Vec<i32> = (0..1_000_000).collect();
+//! let incremented = numbers.par_map(|&x| lib::increment(x));
+//! assert_eq!(incremented[999_999], 1_000_000);
+//!
+//! let even = numbers.par_filter(|&x| x % 2 == 0);
+//! assert_eq!(even.len(), 500_000);
+//! let sum = numbers.par_map(|&x| i64::from(x)).par_reduce(|a, b| a + b);
+//! assert_eq!(sum, Some(499_999_500_000));
+//! ```
+
+use std::cell::Cell;
+use std::panic;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+
+/// The chunks per thread, for balancing.
+const CHUNKS_PER_THREAD: usize = 4;
+
+/// 0 for the available parallelism
+static THREADS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static LOCAL_THREADS: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// The number of threads of the operations called from this thread.
+pub fn threads() -> usize {
+    let threads = LOCAL_THREADS
+        .get()
+        .unwrap_or_else(|| THREADS.load(Ordering::Relaxed));
+    match threads {
+        0 => thread::available_parallelism().map_or(1, usize::from),
+        threads => threads,
+    }
+}
+
+/// Sets the number of threads of the process, 0 for the available
+/// parallelism.
+pub fn set_threads(threads: usize) {
+    THREADS.store(threads, Ordering::Relaxed);
+}
+
+/// Runs `f` with `threads` threads for the operations called from this
+/// thread, 0 for the available parallelism.
+pub fn with_threads<R>(threads: usize, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<usize>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            LOCAL_THREADS.set(self.0);
+        }
+    }
+
+    let _restore = Restore(LOCAL_THREADS.replace(Some(threads)));
+    f()
+}
+
+/// Parallel versions of iterator adapters, for slices and so for `Vec`.
+pub trait ParallelSlice<T: Sync> {
+    /// The images of the items, in order.
+    fn par_map<U: Send>(&self, f: impl Fn(&T) -> U + Sync) -> Vec<U>;
+
+    /// The items for which `predicate` is true, in order.
+    fn par_filter(&self, predicate: impl Fn(&T) -> bool + Sync) -> Vec<&T>;
+
+    /// Calls `f` on the items, in no particular order.
+    fn par_for_each(&self, f: impl Fn(&T) + Sync);
+
+    /// Combines the items with `f`, `None` if there are none.
+    ///
+    /// Items are combined in order but grouped arbitrarily, so `f` must be
+    /// associative, as `+` or string concatenation, not `-`.
+    fn par_reduce(&self, f: impl Fn(T, T) -> T + Sync) -> Option<T>
+    where
+        T: Clone + Send;
+}
+
+impl<T: Sync> ParallelSlice<T> for [T] {
+    fn par_map<U: Send>(&self, f: impl Fn(&T) -> U + Sync) -> Vec<U> {
+        let chunks = run(self, |chunk| chunk.iter().map(&f).collect::<Vec<_>>());
+        chunks.into_iter().flatten().collect()
+    }
+
+    fn par_filter(&self, predicate: impl Fn(&T) -> bool + Sync) -> Vec<&T> {
+        let chunks = run(self, |chunk| {
+            chunk
+                .iter()
+                .filter(|item| predicate(item))
+                .collect::<Vec<_>>()
+        });
+        chunks.into_iter().flatten().collect()
+    }
+
+    fn par_for_each(&self, f: impl Fn(&T) + Sync) {
+        run(self, |chunk| chunk.iter().for_each(&f));
+    }
+
+    fn par_reduce(&self, f: impl Fn(T, T) -> T + Sync) -> Option<T>
+    where
+        T: Clone + Send,
+    {
+        let chunks = run(self, |chunk| chunk.iter().cloned().reduce(&f));
+        chunks.into_iter().flatten().reduce(&f)
+    }
+}
+
+/// The results of `process` on the chunks of `items`, in order.
+fn run<'a, T: Sync, R: Send>(items: &'a [T], process: impl Fn(&'a [T]) -> R + Sync) -> Vec<R> {
+    if items.is_empty() {
+        return Vec::new();
+    }
+    let threads = threads().clamp(1, items.len());
+    let chunk_size = items.len().div_ceil(threads * CHUNKS_PER_THREAD);
+    let chunks: Vec<&'a [T]> = items.chunks(chunk_size).collect();
+    if threads == 1 {
+        return chunks.into_iter().map(process).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let _stop = StopOnPanic(&stop);
+                    let mut results = Vec::new();
+                    while !stop.load(Ordering::Relaxed) {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(chunk) = chunks.get(index) else {
+                            break;
+                        };
+                        results.push((index, process(chunk)));
+                    }
+                    results
+                })
+            })
+            .collect();
+
+        let mut results = Vec::with_capacity(chunks.len());
+        let mut panic = None;
+        for worker in workers {
+            match worker.join() {
+                Ok(worker_results) => results.extend(worker_results),
+                Err(payload) => {
+                    panic.get_or_insert(payload);
+                }
+            }
+        }
+        // With the payload of the first panic, `scope` would panic with its
+        // own message
+        if let Some(payload) = panic {
+            panic::resume_unwind(payload);
+        }
+        results
+    });
+    results.sort_unstable_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Stops the other threads when dropped by a panic.
+struct StopOnPanic<'a>(&'a AtomicBool);
+
+impl Drop for StopOnPanic<'_> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.store(true, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! A fixed number of worker threads running jobs from a shared queue.
+//!
+//! The queue is the consumer pattern of `tests/r_22_concurrency.rs`: an
+//! `mpsc` channel whose receiver is shared by the workers behind a mutex.
+
+use crate::error::Error;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Worker threads named `{name}-{index}`, `pool` by default.
+///
+/// A panicking job doesn't stop its worker. Dropping the pool is a graceful
+/// [`shutdown`](ThreadPool::shutdown), waiting for the queued jobs.
+///
+/// # Examples
+/// ```
+/// use lib::pool::ThreadPool;
+///
+/// let pool = ThreadPool::new(4);
+/// let handles: Vec<_> = (0..8).map(|x| pool.spawn(move || lib::increment(x))).collect();
+/// let values: Vec<i32> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+/// assert_eq!(values, [1, 2, 3, 4, 5, 6, 7, 8]);
+///
+/// let failed = pool.spawn(|| panic!("job failed"));
+/// assert!(failed.join().is_err());
+/// assert_eq!(pool.spawn(|| 1).join().ok(), Some(1));
+/// ```
+pub struct ThreadPool {
+    sender: Option<mpsc::Sender<Job>>,
+    workers: Vec<thread::JoinHandle<()>>,
+    shared: Arc<Shared>,
+}
+
+#[derive(Default)]
+struct Shared {
+    /// Jobs sent and not yet received by a worker
+    queued: AtomicUsize,
+    panicked: AtomicUsize,
+    /// Set by `shutdown_now`, the workers then drop the jobs they receive
+    cancelled: AtomicBool,
+    discarded: AtomicUsize,
+}
+
+impl ThreadPool {
+    pub fn new(threads: usize) -> ThreadPool {
+        ThreadPool::with_name(threads, "pool")
+    }
+
+    /// # Panics
+    /// If `threads` is 0 or a thread can't be created.
+    pub fn with_name(threads: usize, name: &str) -> ThreadPool {
+        assert!(threads > 0, "a thread pool needs at least one thread");
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let shared = Arc::new(Shared::default());
+        let workers = (0..threads)
+            .map(|index| {
+                let receiver = receiver.clone();
+                let shared = shared.clone();
+                thread::Builder::new()
+                    .name(format!("{name}-{index}"))
+                    .spawn(move || work(&receiver, &shared))
+                    .expect("cannot create a worker thread")
+            })
+            .collect();
+        ThreadPool {
+            sender: Some(sender),
+            workers,
+            shared,
+        }
+    }
+
+    /// Queues a job, its panic being caught.
+    pub fn execute<F: FnOnce() + Send + 'static>(&self, job: F) {
+        self.shared.queued.fetch_add(1, Ordering::SeqCst);
+        // Workers hold the receiver until the pool is dropped
+        let sender = self.sender.as_ref().unwrap();
+        sender.send(Box::new(job)).unwrap();
+    }
+
+    /// Queues a job, whose result or panic is returned by the handle.
+    pub fn spawn<T, F>(&self, job: F) -> JoinHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let shared = self.shared.clone();
+        self.execute(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(job));
+            if result.is_err() {
+                shared.panicked.fetch_add(1, Ordering::SeqCst);
+            }
+            // The handle may have been dropped
+            let _ = sender.send(result);
+        });
+        JoinHandle { receiver }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// The jobs waiting for a worker.
+    pub fn queued(&self) -> usize {
+        self.shared.queued.load(Ordering::SeqCst)
+    }
+
+    /// The jobs that panicked so far.
+    pub fn panicked(&self) -> usize {
+        self.shared.panicked.load(Ordering::SeqCst)
+    }
+
+    /// Waits for the queued jobs to run, then for the workers to stop.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    /// Waits for the running jobs only, and returns the number of queued
+    /// jobs dropped. Their handles return [`JoinError::Cancelled`].
+    pub fn shutdown_now(mut self) -> usize {
+        self.shared.cancelled.store(true, Ordering::SeqCst);
+        self.stop();
+        self.shared.discarded.load(Ordering::SeqCst)
+    }
+
+    fn stop(&mut self) {
+        // Workers stop on disconnection, once the queue is empty
+        self.sender.take();
+        for worker in self.workers.drain(..) {
+            // Jobs run under `catch_unwind`, workers don't panic
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn work(receiver: &Mutex<mpsc::Receiver<Job>>, shared: &Shared) {
+    loop {
+        // The lock is released before running the job
+        let job = receiver
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .recv();
+        let Ok(job) = job else {
+            break;
+        };
+        shared.queued.fetch_sub(1, Ordering::SeqCst);
+        if shared.cancelled.load(Ordering::SeqCst) {
+            shared.discarded.fetch_add(1, Ordering::SeqCst);
+            continue;
+        }
+        if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+            shared.panicked.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+// ____________________________________________________________
+// Join handles
+
+/// The result of a job queued by [`ThreadPool::spawn`].
+pub struct JoinHandle<T> {
+    receiver: mpsc::Receiver<thread::Result<T>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Waits for the job to finish.
+    pub fn join(self) -> Result<T, JoinError> {
+        match self.receiver.recv() {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(payload)) => Err(JoinError::Panicked(payload)),
+            // The job was dropped without running
+            Err(mpsc::RecvError) => Err(JoinError::Cancelled),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum JoinError {
+    /// With the payload of the panic, to resume it with
+    /// `std::panic::resume_unwind`
+    #[error("the job panicked")]
+    Panicked(Box<dyn Any + Send>),
+    #[error("the job was cancelled by the shutdown of the pool")]
+    Cancelled,
+}
+
+impl JoinError {
+    /// The message of a panic with a string message.
+    pub fn panic_message(&self) -> Option<&str> {
+        let JoinError::Panicked(payload) = self else {
+            return None;
+        };
+        payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+    }
+}
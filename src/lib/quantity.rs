@@ -0,0 +1,157 @@
+//! Support for the [`quantity!`](crate::quantity!) macro.
+
+/// A unit of measure of the quantity `Self::Quantity`.
+///
+/// The quantity is the generic type instantiated with the unit, so that a
+/// unit can't be used with a quantity it wasn't declared for.
+pub trait Unit: Copy {
+    type Quantity;
+    /// The size of the unit in base units
+    const FACTOR: f64;
+    const SYMBOL: &'static str;
+}
+
+/// Generates a quantity generic over its units, as the phantom-type
+/// `Length<Unit>` in `tests/r_05_generics.rs`.
+///
+/// Each unit is a marker type with its size in base units and an optional
+/// symbol, which defaults to the unit name. Values are stored in base units,
+/// so that `convert::<U>()` only changes the type and never loses precision.
+/// Quantities implement `Add`, `Sub`, `Neg`, `Mul<f64>` and `Div<f64>`, and
+/// `Display` with the symbol of their unit.
+///
+/// # Examples
+/// ```
+/// lib::quantity!(pub Length: Meter("m") = 1.0, Mile("mi") = 1609.344, Kilometer("km") = 1000.0);
+///
+/// let one_mile = Length::<Mile>::new(1.0);
+/// let distance = one_mile * 2.0 - Length::new(0.5);
+/// assert_eq!(distance.to_string(), "1.5 mi");
+/// assert_eq!(format!("{:.3}", distance.convert::<Kilometer>()), "2.414 km");
+/// assert_eq!(one_mile.convert::<Meter>().convert::<Mile>(), one_mile);
+/// ```
+///
+/// Quantities in different units can't be added:
+/// ```compile_fail
+/// lib::quantity!(Length: Meter = 1.0, Mile = 1609.344);
+///
+/// let one_meter = Length::<Meter>::new(1.0);
+/// let one_mile = Length::<Mile>::new(1.0);
+/// let x = one_mile + one_meter;
+/// ```
+///
+/// Nor used with the units of other quantities:
+/// ```compile_fail
+/// lib::quantity!(Length: Meter = 1.0);
+/// lib::quantity!(Mass: Kilogram = 1.0);
+///
+/// let x = Length::<Kilogram>::new(1.0);
+/// ```
+#[macro_export]
+macro_rules! quantity {
+    (@symbol $unit:ident) => {
+        ::std::stringify!($unit)
+    };
+    (@symbol $unit:ident $symbol:literal) => {
+        $symbol
+    };
+    (
+        $(#[$meta:meta])*
+        $vis:vis $name:ident: $($unit:ident $(($symbol:literal))? = $factor:expr),+ $(,)?
+    ) => {
+        $(
+            #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            $vis struct $unit;
+
+            impl $crate::quantity::Unit for $unit {
+                type Quantity = $name<$unit>;
+                const FACTOR: f64 = $factor;
+                const SYMBOL: &'static str = $crate::quantity!(@symbol $unit $($symbol)?);
+            }
+        )+
+
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+        $vis struct $name<U> {
+            base: f64,
+            unit: ::std::marker::PhantomData<U>,
+        }
+
+        impl<U: $crate::quantity::Unit<Quantity = $name<U>>> $name<U> {
+            pub fn new(value: f64) -> $name<U> {
+                $name::from_base(value * U::FACTOR)
+            }
+
+            fn from_base(base: f64) -> $name<U> {
+                $name {
+                    base,
+                    unit: ::std::marker::PhantomData,
+                }
+            }
+
+            /// The value in `U`
+            pub fn value(self) -> f64 {
+                self.base / U::FACTOR
+            }
+
+            pub fn convert<V: $crate::quantity::Unit<Quantity = $name<V>>>(self) -> $name<V> {
+                $name::from_base(self.base)
+            }
+        }
+
+        impl<U: $crate::quantity::Unit<Quantity = $name<U>>> ::std::ops::Add for $name<U> {
+            type Output = $name<U>;
+
+            fn add(self, rhs: $name<U>) -> $name<U> {
+                $name::from_base(self.base + rhs.base)
+            }
+        }
+
+        impl<U: $crate::quantity::Unit<Quantity = $name<U>>> ::std::ops::Sub for $name<U> {
+            type Output = $name<U>;
+
+            fn sub(self, rhs: $name<U>) -> $name<U> {
+                $name::from_base(self.base - rhs.base)
+            }
+        }
+
+        impl<U: $crate::quantity::Unit<Quantity = $name<U>>> ::std::ops::Neg for $name<U> {
+            type Output = $name<U>;
+
+            fn neg(self) -> $name<U> {
+                $name::from_base(-self.base)
+            }
+        }
+
+        impl<U: $crate::quantity::Unit<Quantity = $name<U>>> ::std::ops::Mul<f64> for $name<U> {
+            type Output = $name<U>;
+
+            fn mul(self, rhs: f64) -> $name<U> {
+                $name::from_base(self.base * rhs)
+            }
+        }
+
+        impl<U: $crate::quantity::Unit<Quantity = $name<U>>> ::std::ops::Mul<$name<U>> for f64 {
+            type Output = $name<U>;
+
+            fn mul(self, rhs: $name<U>) -> $name<U> {
+                rhs * self
+            }
+        }
+
+        impl<U: $crate::quantity::Unit<Quantity = $name<U>>> ::std::ops::Div<f64> for $name<U> {
+            type Output = $name<U>;
+
+            fn div(self, rhs: f64) -> $name<U> {
+                $name::from_base(self.base / rhs)
+            }
+        }
+
+        impl<U: $crate::quantity::Unit<Quantity = $name<U>>> ::std::fmt::Display for $name<U> {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                ::std::fmt::Display::fmt(&self.value(), f)?;
+                write!(f, " {}", U::SYMBOL)
+            }
+        }
+    };
+}
@@ -0,0 +1,287 @@
+//! A global registry of types, their layout and some of their traits.
+//!
+//! Types are registered once with [`register_type!`](crate::register_type!)
+//! and then looked up by `TypeId` or name, from any thread.
+
+use crate::describe::{Describe, FieldDescription, TypeDescription, TypeKind};
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::{LazyLock, RwLock};
+
+/// The traits looked for by [`register_type!`](crate::register_type!).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Trait {
+    Debug,
+    Display,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Send,
+    Sync,
+    Unpin,
+}
+
+impl Trait {
+    pub const ALL: &'static [Trait] = &[
+        Trait::Debug,
+        Trait::Display,
+        Trait::Clone,
+        Trait::Copy,
+        Trait::Default,
+        Trait::PartialEq,
+        Trait::Eq,
+        Trait::PartialOrd,
+        Trait::Ord,
+        Trait::Hash,
+        Trait::Send,
+        Trait::Sync,
+        Trait::Unpin,
+    ];
+}
+
+impl fmt::Display for Trait {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeInfo {
+    pub id: TypeId,
+    pub description: TypeDescription,
+    /// Sorted as [`Trait::ALL`]
+    pub traits: Vec<Trait>,
+}
+
+impl TypeInfo {
+    pub fn name(&self) -> &'static str {
+        self.description.name
+    }
+
+    pub fn implements(&self, tr: Trait) -> bool {
+        self.traits.contains(&tr)
+    }
+}
+
+impl fmt::Display for TypeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = &self.description;
+        let kind = match description.kind {
+            TypeKind::Struct => "struct",
+            TypeKind::Enum => "enum",
+        };
+        write!(
+            f,
+            "{kind} {}, size {}, align {}",
+            description.name, description.size, description.align
+        )?;
+        if !self.traits.is_empty() {
+            let traits: Vec<_> = self.traits.iter().map(Trait::to_string).collect();
+            write!(f, "\n    implements {}", traits.join(", "))?;
+        }
+        for field in &description.fields {
+            write!(f, "\n    ")?;
+            if let Some(offset) = field.offset {
+                write!(f, "{offset:>4}  ")?;
+            }
+            write!(f, "{}: {}", field.name, field.type_name)?;
+        }
+        for variant in &description.variants {
+            write!(f, "\n    {}", variant.name)?;
+            if !variant.fields.is_empty() {
+                let fields: Vec<_> = variant.fields.iter().map(field_to_string).collect();
+                write!(f, " {{ {} }}", fields.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn field_to_string(field: &FieldDescription) -> String {
+    format!("{}: {}", field.name, field.type_name)
+}
+
+// ____________________________________________________________
+// Registry
+
+#[derive(Default)]
+struct Registry {
+    /// In registration order, leaked so that lookups don't hold the lock
+    types: Vec<&'static TypeInfo>,
+    by_id: HashMap<TypeId, &'static TypeInfo>,
+    by_name: HashMap<&'static str, &'static TypeInfo>,
+}
+
+static REGISTRY: LazyLock<RwLock<Registry>> = LazyLock::new(Default::default);
+
+/// Registers `T` with the given traits, unless it is already registered.
+///
+/// [`register_type!`](crate::register_type!) finds the traits itself.
+pub fn insert<T: Describe + 'static>(traits: &[Trait]) -> &'static TypeInfo {
+    if let Some(info) = get(TypeId::of::<T>()) {
+        return info;
+    }
+    let mut registry = REGISTRY.write().unwrap_or_else(|error| error.into_inner());
+    // Another thread may have registered it in between
+    if let Some(info) = registry.by_id.get(&TypeId::of::<T>()) {
+        return info;
+    }
+    let mut traits = traits.to_vec();
+    traits.sort();
+    traits.dedup();
+    let info: &'static TypeInfo = Box::leak(Box::new(TypeInfo {
+        id: TypeId::of::<T>(),
+        description: T::describe(),
+        traits,
+    }));
+    registry.types.push(info);
+    registry.by_id.insert(info.id, info);
+    registry.by_name.insert(info.name(), info);
+    info
+}
+
+pub fn get(id: TypeId) -> Option<&'static TypeInfo> {
+    let registry = REGISTRY.read().unwrap_or_else(|error| error.into_inner());
+    registry.by_id.get(&id).copied()
+}
+
+pub fn get_of<T: 'static>() -> Option<&'static TypeInfo> {
+    get(TypeId::of::<T>())
+}
+
+/// Lookup by `std::any::type_name`.
+pub fn by_name(name: &str) -> Option<&'static TypeInfo> {
+    let registry = REGISTRY.read().unwrap_or_else(|error| error.into_inner());
+    registry.by_name.get(name).copied()
+}
+
+/// The types registered so far, in registration order.
+pub fn types() -> impl Iterator<Item = &'static TypeInfo> {
+    let registry = REGISTRY.read().unwrap_or_else(|error| error.into_inner());
+    registry.types.clone().into_iter()
+}
+
+// ____________________________________________________________
+// Trait detection
+
+// Autoref specialization: `Yes` applies to `Probe<T>` when `T` implements
+// the trait, otherwise method resolution falls back to `No` on `&Probe<T>`.
+// It only works on concrete types, hence the macro.
+
+#[doc(hidden)]
+pub struct Probe<T: ?Sized>(pub PhantomData<T>);
+
+macro_rules! probes {
+    ($($name:ident: $bound:path,)*) => {
+        #[doc(hidden)]
+        #[allow(non_snake_case)]
+        pub mod probe {
+            $(
+                pub mod $name {
+                    use super::super::Probe;
+
+                    pub trait Yes {
+                        fn implements(&self) -> bool {
+                            true
+                        }
+                    }
+
+                    impl<T: ?Sized + $bound> Yes for Probe<T> {}
+
+                    pub trait No {
+                        fn implements(&self) -> bool {
+                            false
+                        }
+                    }
+
+                    impl<T: ?Sized> No for &Probe<T> {}
+                }
+            )*
+        }
+    };
+}
+
+probes! {
+    Debug: std::fmt::Debug,
+    Display: std::fmt::Display,
+    Clone: std::clone::Clone,
+    Copy: std::marker::Copy,
+    Default: std::default::Default,
+    PartialEq: std::cmp::PartialEq,
+    Eq: std::cmp::Eq,
+    PartialOrd: std::cmp::PartialOrd,
+    Ord: std::cmp::Ord,
+    Hash: std::hash::Hash,
+    Send: std::marker::Send,
+    Sync: std::marker::Sync,
+    Unpin: std::marker::Unpin,
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __implements {
+    ($ty:ty, $name:ident) => {{
+        use $crate::registry::probe::$name::{No as _, Yes as _};
+        (&$crate::registry::Probe::<$ty>(::std::marker::PhantomData))
+            .implements()
+            .then_some($crate::registry::Trait::$name)
+    }};
+}
+
+/// Registers a type implementing [`Describe`], with the traits of
+/// [`Trait::ALL`] it implements, and returns its [`TypeInfo`].
+///
+/// Registering a type again returns the first registration. The type must
+/// be concrete: in generic code, the traits of a type parameter are only the
+/// ones of its bounds.
+///
+/// # Examples
+/// ```
+/// use lib::describe::Describe;
+/// use lib::registry::{self, Trait};
+///
+/// #[derive(Debug, Clone, Copy, Describe)]
+/// #[repr(C)]
+/// struct Point {
+///     x: u8,
+///     y: u32,
+/// }
+///
+/// let info = lib::register_type!(Point);
+/// assert_eq!(info.description.size, 8);
+/// assert_eq!(info.description.field("y").unwrap().offset, Some(4));
+/// assert!(info.implements(Trait::Copy) && !info.implements(Trait::Default));
+/// assert_eq!(registry::get_of::<Point>(), Some(info));
+/// assert_eq!(registry::by_name(std::any::type_name::<Point>()), Some(info));
+/// ```
+#[macro_export]
+macro_rules! register_type {
+    ($ty:ty) => {{
+        let traits = [
+            $crate::__implements!($ty, Debug),
+            $crate::__implements!($ty, Display),
+            $crate::__implements!($ty, Clone),
+            $crate::__implements!($ty, Copy),
+            $crate::__implements!($ty, Default),
+            $crate::__implements!($ty, PartialEq),
+            $crate::__implements!($ty, Eq),
+            $crate::__implements!($ty, PartialOrd),
+            $crate::__implements!($ty, Ord),
+            $crate::__implements!($ty, Hash),
+            $crate::__implements!($ty, Send),
+            $crate::__implements!($ty, Sync),
+            $crate::__implements!($ty, Unpin),
+        ];
+        let traits: ::std::vec::Vec<_> = traits.into_iter().flatten().collect();
+        $crate::registry::insert::<$ty>(&traits)
+    }};
+}
+
+pub use crate::register_type;
@@ -0,0 +1,217 @@
+//! Rendering of errors with their whole `source()` chain.
+
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::error::Error;
+use std::fmt::{self, Write};
+
+/// An error with its causes and, optionally, the backtrace of its creation.
+///
+/// `Display` renders the compact form, or the pretty one with `{:#}`, and
+/// `Debug` the pretty one, so that `main` can return `Result<(), Report>`.
+///
+/// # Examples
+/// ```
+/// use lib::report::Report;
+/// use std::{error::Error, fmt, io};
+///
+/// #[derive(Debug)]
+/// struct ConfigError(io::Error);
+///
+/// impl fmt::Display for ConfigError {
+///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         write!(f, "cannot read the configuration")
+///     }
+/// }
+///
+/// impl Error for ConfigError {
+///     fn source(&self) -> Option<&(dyn Error + 'static)> {
+///         Some(&self.0)
+///     }
+/// }
+///
+/// let cause = io::Error::new(io::ErrorKind::NotFound, "config.toml not found");
+/// let report = Report::new(ConfigError(cause));
+/// assert_eq!(
+///     report.compact().to_string(),
+///     "cannot read the configuration: config.toml not found"
+/// );
+/// assert_eq!(
+///     report.pretty().to_string(),
+///     "Error: cannot read the configuration\n\nCaused by:\n    1: config.toml not found"
+/// );
+/// ```
+pub struct Report {
+    error: Box<dyn Error + 'static>,
+    backtrace: Option<Backtrace>,
+}
+
+impl Report {
+    pub fn new(error: impl Into<Box<dyn Error + 'static>>) -> Report {
+        Report {
+            error: error.into(),
+            backtrace: None,
+        }
+    }
+
+    /// Captures a backtrace if enabled by `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE`.
+    pub fn with_backtrace(error: impl Into<Box<dyn Error + 'static>>) -> Report {
+        Report {
+            error: error.into(),
+            backtrace: Some(Backtrace::capture()),
+        }
+    }
+
+    pub fn error(&self) -> &(dyn Error + 'static) {
+        &*self.error
+    }
+
+    pub fn into_error(self) -> Box<dyn Error + 'static> {
+        self.error
+    }
+
+    /// The error followed by its sources.
+    pub fn chain(&self) -> impl Iterator<Item = &(dyn Error + 'static)> {
+        std::iter::successors(Some(self.error()), |&error| error.source())
+    }
+
+    /// The backtrace, if one was captured.
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        self.backtrace
+            .as_ref()
+            .filter(|backtrace| backtrace.status() == BacktraceStatus::Captured)
+    }
+
+    /// All the messages on a single line, separated by `: `.
+    pub fn compact(&self) -> Compact<'_> {
+        Compact(self)
+    }
+
+    /// The error, its numbered causes and the backtrace on multiple lines.
+    pub fn pretty(&self) -> Pretty<'_> {
+        Pretty(self)
+    }
+
+    /// A JSON object with the `error`, `causes` and `backtrace` keys.
+    pub fn json(&self) -> Json<'_> {
+        Json(self)
+    }
+}
+
+impl<E: Error + 'static> From<E> for Report {
+    fn from(error: E) -> Report {
+        Report::new(error)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            fmt::Display::fmt(&self.pretty(), f)
+        } else {
+            fmt::Display::fmt(&self.compact(), f)
+        }
+    }
+}
+
+impl fmt::Debug for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.pretty(), f)
+    }
+}
+
+// ____________________________________________________________
+// Formats
+
+pub struct Compact<'a>(&'a Report);
+
+impl fmt::Display for Compact<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, error) in self.0.chain().enumerate() {
+            if index > 0 {
+                f.write_str(": ")?;
+            }
+            // The lines of a multi-line message are joined by spaces
+            for (line, text) in error.to_string().lines().enumerate() {
+                if line > 0 {
+                    f.write_str(" ")?;
+                }
+                f.write_str(text)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct Pretty<'a>(&'a Report);
+
+impl fmt::Display for Pretty<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error: ")?;
+        write_indented(f, &self.0.error.to_string(), "       ")?;
+
+        let mut causes = self.0.chain().skip(1).peekable();
+        if causes.peek().is_some() {
+            write!(f, "\n\nCaused by:")?;
+            for (index, cause) in causes.enumerate() {
+                let number = format!("{:>5}: ", index + 1);
+                write!(f, "\n{number}")?;
+                write_indented(f, &cause.to_string(), &" ".repeat(number.len()))?;
+            }
+        }
+
+        if let Some(backtrace) = self.0.backtrace() {
+            write!(f, "\n\nBacktrace:\n")?;
+            write_indented(f, backtrace.to_string().trim_end(), "")?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes `text`, indenting the lines after the first.
+fn write_indented(f: &mut fmt::Formatter<'_>, text: &str, indentation: &str) -> fmt::Result {
+    for (index, line) in text.lines().enumerate() {
+        if index > 0 {
+            write!(f, "\n{indentation}")?;
+        }
+        f.write_str(line)?;
+    }
+    Ok(())
+}
+
+pub struct Json<'a>(&'a Report);
+
+impl fmt::Display for Json<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("{\"error\":")?;
+        write_json_string(f, &self.0.error.to_string())?;
+        f.write_str(",\"causes\":[")?;
+        for (index, cause) in self.0.chain().skip(1).enumerate() {
+            if index > 0 {
+                f.write_char(',')?;
+            }
+            write_json_string(f, &cause.to_string())?;
+        }
+        f.write_str("],\"backtrace\":")?;
+        match self.0.backtrace() {
+            Some(backtrace) => write_json_string(f, &backtrace.to_string())?,
+            None => f.write_str("null")?,
+        }
+        f.write_char('}')
+    }
+}
+
+pub(crate) fn write_json_string(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in text.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
@@ -0,0 +1,261 @@
+//! Support for the [`state_machine!`](crate::state_machine!) macro.
+
+use std::error::Error;
+use std::fmt;
+
+/// The event has no transition from the state, or all its guards failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidTransition<S, E> {
+    pub state: S,
+    pub event: E,
+}
+
+impl<S: fmt::Debug, E: fmt::Debug> fmt::Display for InvalidTransition<S, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no transition from {:?} on {:?}", self.state, self.event)
+    }
+}
+
+impl<S: fmt::Debug, E: fmt::Debug> Error for InvalidTransition<S, E> {}
+
+// Calling closures through these lets their argument types be inferred.
+
+#[doc(hidden)]
+pub fn guard<C>(guard: impl FnOnce(&C) -> bool, context: &C) -> bool {
+    guard(context)
+}
+
+#[doc(hidden)]
+pub fn action<C>(action: impl FnOnce(&mut C), context: &mut C) {
+    action(context)
+}
+
+#[doc(hidden)]
+pub fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Generates a module with a state machine.
+///
+/// The module contains the `State` and `Event` enums, the `Machine` struct
+/// owning the current state and a context, and the `InvalidTransition` error.
+/// `Machine::transition` takes the first transition of the current state
+/// whose guard accepts the context, running the exit action of the old state
+/// and then the entry action of the new one.
+///
+/// The `context`, `enter` and `exit` sections are optional. The context
+/// defaults to `()`. Guards and actions are closures or functions taking
+/// `&Context` and `&mut Context`.
+///
+/// States unreachable from the initial state and events without any
+/// transition are compile errors, guards are not taken into account.
+///
+/// # Examples
+/// ```
+/// lib::state_machine! {
+///     pub mod door {
+///         context: u32,
+///         initial: Closed,
+///         states: { Opened, Closed, Locked },
+///         events: { Open, Close, Lock, Unlock },
+///         transitions: {
+///             Closed + Open => Opened,
+///             Opened + Close => Closed,
+///             Closed + Lock if |keys| *keys > 0 => Locked,
+///             Locked + Unlock => Closed,
+///         },
+///         enter: { Opened => |openings| *openings += 1 },
+///     }
+/// }
+/// use door::{Event, State};
+///
+/// let mut door = door::Machine::new(0);
+/// assert_eq!(door.transition(Event::Open), Ok(State::Opened));
+/// assert!(door.transition(Event::Lock).is_err());
+/// assert_eq!(door.transition(Event::Close), Ok(State::Closed));
+/// assert_eq!(door.transition(Event::Lock), Ok(State::Locked));
+/// assert_eq!(*door.context(), 1);
+/// assert!(door::Machine::dot().contains("Closed -> Locked"));
+/// ```
+///
+/// ```compile_fail
+/// lib::state_machine! {
+///     mod light {
+///         initial: Off,
+///         states: { On, Off, Broken },
+///         events: { Toggle },
+///         transitions: { Off + Toggle => On, On + Toggle => Off },
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! state_machine {
+    (
+        $(#[$meta:meta])*
+        $vis:vis mod $name:ident {
+            initial: $($rest:tt)*
+        }
+    ) => {
+        $crate::state_machine! {
+            $(#[$meta])*
+            $vis mod $name {
+                context: (),
+                initial: $($rest)*
+            }
+        }
+    };
+    (
+        $(#[$meta:meta])*
+        $vis:vis mod $name:ident {
+            context: $context:ty,
+            initial: $initial:ident,
+            states: { $($state:ident),+ $(,)? },
+            events: { $($event:ident),+ $(,)? },
+            transitions: {
+                $($from:ident + $on:ident $(if $guard:expr)? => $to:ident),+ $(,)?
+            }
+            $(, enter: { $($enter_state:ident => $enter:expr),* $(,)? })?
+            $(, exit: { $($exit_state:ident => $exit:expr),* $(,)? })?
+            $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis mod $name {
+            #![allow(dead_code, unused_imports)]
+            use super::*;
+
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub enum State {
+                $($state),+
+            }
+
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub enum Event {
+                $($event),+
+            }
+
+            pub type InvalidTransition = $crate::state_machine::InvalidTransition<State, Event>;
+
+            pub struct Machine {
+                state: State,
+                context: $context,
+            }
+
+            impl Machine {
+                pub const INITIAL: State = State::$initial;
+
+                /// Starts in the initial state, without running its entry action.
+                pub fn new(context: $context) -> Machine {
+                    Machine {
+                        state: State::$initial,
+                        context,
+                    }
+                }
+
+                pub fn state(&self) -> State {
+                    self.state
+                }
+
+                pub fn context(&self) -> &$context {
+                    &self.context
+                }
+
+                pub fn context_mut(&mut self) -> &mut $context {
+                    &mut self.context
+                }
+
+                pub fn into_context(self) -> $context {
+                    self.context
+                }
+
+                pub fn transition(&mut self, event: Event) -> Result<State, InvalidTransition> {
+                    let from = self.state;
+                    $(
+                        if from == State::$from
+                            && event == Event::$on
+                            $(&& $crate::state_machine::guard($guard, &self.context))?
+                        {
+                            self.exit(from);
+                            self.state = State::$to;
+                            self.enter(State::$to);
+                            return Ok(State::$to);
+                        }
+                    )+
+                    Err(InvalidTransition { state: from, event })
+                }
+
+                #[allow(unused_variables)]
+                fn enter(&mut self, state: State) {
+                    $($(
+                        if state == State::$enter_state {
+                            $crate::state_machine::action($enter, &mut self.context);
+                        }
+                    )*)?
+                }
+
+                #[allow(unused_variables)]
+                fn exit(&mut self, state: State) {
+                    $($(
+                        if state == State::$exit_state {
+                            $crate::state_machine::action($exit, &mut self.context);
+                        }
+                    )*)?
+                }
+
+                /// The machine in the Graphviz DOT language.
+                pub fn dot() -> String {
+                    let mut dot = String::new();
+                    dot.push_str(concat!("digraph ", stringify!($name), " {\n"));
+                    dot.push_str("    __start [shape=point];\n");
+                    dot.push_str(concat!("    __start -> ", stringify!($initial), ";\n"));
+                    $(dot.push_str(concat!("    ", stringify!($state), ";\n"));)+
+                    $(
+                        let label = $crate::state_machine::dot_escape(concat!(
+                            stringify!($on) $(, " [", stringify!($guard), "]")?
+                        ));
+                        dot.push_str(&format!(
+                            concat!("    ", stringify!($from), " -> ", stringify!($to), " [label=\"{}\"];\n"),
+                            label
+                        ));
+                    )+
+                    dot.push_str("}\n");
+                    dot
+                }
+            }
+
+            // Reachability and event coverage, ignoring guards
+            const _: () = {
+                const STATES: usize = [$(State::$state),+].len();
+                const EVENTS: usize = [$(Event::$event),+].len();
+                const TRANSITIONS: &[(usize, usize, usize)] =
+                    &[$((State::$from as usize, Event::$on as usize, State::$to as usize)),+];
+
+                let mut reachable = [false; STATES];
+                reachable[State::$initial as usize] = true;
+                let mut handled = [false; EVENTS];
+                let mut changed = true;
+                while changed {
+                    changed = false;
+                    let mut i = 0;
+                    while i < TRANSITIONS.len() {
+                        let (from, event, to) = TRANSITIONS[i];
+                        handled[event] = true;
+                        if reachable[from] && !reachable[to] {
+                            reachable[to] = true;
+                            changed = true;
+                        }
+                        i += 1;
+                    }
+                }
+                $(assert!(
+                    reachable[State::$state as usize],
+                    concat!("state `", stringify!($state), "` is unreachable from `", stringify!($initial), "`")
+                );)+
+                $(assert!(
+                    handled[Event::$event as usize],
+                    concat!("event `", stringify!($event), "` has no transition")
+                );)+
+            };
+        }
+    };
+}
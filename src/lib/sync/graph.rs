@@ -0,0 +1,162 @@
+//! The global lock-order graph and the locks held by each thread.
+
+use super::{Acquisition, PotentialDeadlock};
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::panic::Location;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Edges from a lock to the locks acquired while holding it.
+static GRAPH: Mutex<BTreeMap<usize, BTreeMap<usize, Acquisition>>> = Mutex::new(BTreeMap::new());
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+thread_local! {
+    /// In acquisition order
+    static HELD: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The number of a lock, assigned on first use so that locks can be created
+/// in a `const` context. Its edges are removed when it is dropped.
+#[derive(Debug)]
+pub(super) struct LockId(AtomicUsize);
+
+impl LockId {
+    pub(super) const fn new() -> LockId {
+        LockId(AtomicUsize::new(0))
+    }
+
+    fn get(&self) -> usize {
+        let id = self.0.load(Ordering::Relaxed);
+        if id != 0 {
+            return id;
+        }
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        match self
+            .0
+            .compare_exchange(0, id, Ordering::Relaxed, Ordering::Relaxed)
+        {
+            Ok(_) => id,
+            Err(other) => other,
+        }
+    }
+
+    /// Records a blocking acquisition, before waiting for the lock, so that
+    /// a deadlock is reported even if it happens.
+    pub(super) fn acquire(&self, location: &'static Location<'static>) -> Held {
+        let id = self.get();
+        let held = HELD.with(|held| held.borrow().clone());
+        let deadlocks = record(id, &held, location);
+        for deadlock in &deadlocks {
+            super::report(deadlock);
+        }
+        self.acquired()
+    }
+
+    /// Records a non-blocking acquisition, which can't deadlock.
+    pub(super) fn acquired(&self) -> Held {
+        let id = self.get();
+        HELD.with(|held| held.borrow_mut().push(id));
+        Held(id)
+    }
+}
+
+impl Drop for LockId {
+    fn drop(&mut self) {
+        let id = *self.0.get_mut();
+        if id == 0 {
+            return;
+        }
+        let mut graph = GRAPH.lock().unwrap_or_else(|error| error.into_inner());
+        graph.remove(&id);
+        for locks in graph.values_mut() {
+            locks.remove(&id);
+        }
+    }
+}
+
+/// A lock held by the current thread, until dropped.
+#[derive(Debug)]
+pub(super) struct Held(usize);
+
+impl Drop for Held {
+    fn drop(&mut self) {
+        // Guards may be dropped in any order, and while the thread exits
+        let _ = HELD.try_with(|held| {
+            let mut held = held.borrow_mut();
+            if let Some(index) = held.iter().rposition(|&id| id == self.0) {
+                held.remove(index);
+            }
+        });
+    }
+}
+
+/// Adds the edges from the `held` locks to `lock`, returning the cycles
+/// they close.
+fn record(
+    lock: usize,
+    held: &[usize],
+    location: &'static Location<'static>,
+) -> Vec<PotentialDeadlock> {
+    let mut deadlocks = Vec::new();
+    let mut backtrace = None;
+    // The reporters are called after releasing the graph
+    let mut graph = GRAPH.lock().unwrap_or_else(|error| error.into_inner());
+    for &from in held {
+        let known = graph
+            .get(&from)
+            .is_some_and(|locks| locks.contains_key(&lock));
+        // Locking a lock already held is not an ordering problem
+        if from == lock || known {
+            continue;
+        }
+        let backtrace = backtrace.get_or_insert_with(|| Arc::new(Backtrace::force_capture()));
+        let acquisition = Acquisition {
+            lock,
+            held: from,
+            location,
+            thread: thread::current().name().unwrap_or("<unnamed>").to_string(),
+            backtrace: backtrace.clone(),
+        };
+        if let Some(path) = path(&graph, lock, from) {
+            let mut acquisitions = vec![acquisition.clone()];
+            acquisitions.extend(path);
+            deadlocks.push(PotentialDeadlock { acquisitions });
+        }
+        graph.entry(from).or_default().insert(lock, acquisition);
+    }
+    deadlocks
+}
+
+/// The edges of a shortest path from `start` to `end`.
+fn path(
+    graph: &BTreeMap<usize, BTreeMap<usize, Acquisition>>,
+    start: usize,
+    end: usize,
+) -> Option<Vec<Acquisition>> {
+    let mut previous: BTreeMap<usize, &Acquisition> = BTreeMap::new();
+    let mut queue = VecDeque::from([start]);
+    while let Some(from) = queue.pop_front() {
+        if from == end {
+            let mut path = Vec::new();
+            let mut lock = end;
+            while lock != start {
+                let acquisition = previous[&lock];
+                path.push(acquisition.clone());
+                lock = acquisition.held;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        for (&to, acquisition) in graph.get(&from).into_iter().flatten() {
+            if to != start && !previous.contains_key(&to) {
+                previous.insert(to, acquisition);
+                queue.push_back(to);
+            }
+        }
+    }
+    None
+}
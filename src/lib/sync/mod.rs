@@ -0,0 +1,147 @@
+//! `Mutex`, `RwLock` and `Condvar` checking the order in which locks are
+//! acquired, in debug builds.
+//!
+//! Each thread records the locks it holds. Acquiring a lock while holding
+//! others adds edges to a global lock-order graph, and an edge closing a
+//! cycle is a potential deadlock: two threads following the cycle in
+//! different orders could each wait for the other. It is reported with the
+//! backtraces of the acquisitions of the cycle, whether or not the threads
+//! actually deadlocked.
+//!
+//! In release builds, the types are the ones of `std::sync`. Reports go to
+//! the reporter installed on the current thread by [`with_reporter`],
+//! otherwise to the global one set by [`set_reporter`], which defaults to
+//! stderr.
+//!
+//! # Examples
+//! ```
+//! use lib::sync::{self, Mutex};
+//! use std::sync::Arc;
+//!
+//! let accounts = Mutex::new(100);
+//! let audit = Mutex::new(Vec::new());
+//! let reports = Arc::new(Mutex::new(0));
+//! let reported = reports.clone();
+//! sync::with_reporter(move |_| *reported.lock().unwrap() += 1, || {
+//!     {
+//!         let balance = accounts.lock().unwrap();
+//!         audit.lock().unwrap().push(*balance);
+//!     }
+//!     // The reverse order, a deadlock with the block above on another thread
+//!     let audit = audit.lock().unwrap();
+//!     *accounts.lock().unwrap() -= audit.len();
+//! });
+//! assert_eq!(*reports.lock().unwrap(), usize::from(sync::CHECKED));
+//! ```
+
+#[cfg(debug_assertions)]
+mod checked;
+#[cfg(debug_assertions)]
+mod graph;
+
+#[cfg(debug_assertions)]
+pub use checked::{Condvar, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+#[cfg(not(debug_assertions))]
+pub use std::sync::{Condvar, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::fmt;
+use std::panic::Location;
+use std::sync::Arc;
+
+/// Whether the locks are checked, in debug builds of this crate.
+pub const CHECKED: bool = cfg!(debug_assertions);
+
+/// Locks acquired in a cycle, each while holding the previous one.
+#[derive(Debug, Clone)]
+pub struct PotentialDeadlock {
+    /// Starting with the acquisition closing the cycle
+    pub acquisitions: Vec<Acquisition>,
+}
+
+/// The first acquisition of `lock` while holding `held`, locks being
+/// numbered in the order of their first use.
+#[derive(Debug, Clone)]
+pub struct Acquisition {
+    pub lock: usize,
+    pub held: usize,
+    pub location: &'static Location<'static>,
+    pub thread: String,
+    pub backtrace: Arc<Backtrace>,
+}
+
+impl fmt::Display for PotentialDeadlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "potential deadlock, locks acquired in a cycle")?;
+        for acquisition in &self.acquisitions {
+            write!(f, "\n\n{acquisition}\n{}", acquisition.backtrace)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Acquisition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "lock #{} acquired while holding lock #{} at {} on thread `{}`",
+            self.lock, self.held, self.location, self.thread
+        )
+    }
+}
+
+// ____________________________________________________________
+// Reporters
+
+type Reporter = Arc<dyn Fn(&PotentialDeadlock) + Send + Sync>;
+
+// From `std`, not to check the reporters themselves
+static GLOBAL_REPORTER: std::sync::RwLock<Option<Reporter>> = std::sync::RwLock::new(None);
+
+thread_local! {
+    static LOCAL_REPORTER: RefCell<Option<Reporter>> = const { RefCell::new(None) };
+}
+
+/// Replaces the reporter used by threads without a local one.
+pub fn set_reporter(reporter: impl Fn(&PotentialDeadlock) + Send + Sync + 'static) {
+    let mut global = GLOBAL_REPORTER
+        .write()
+        .unwrap_or_else(|error| error.into_inner());
+    *global = Some(Arc::new(reporter));
+}
+
+/// Sends the potential deadlocks found by the current thread to `reporter`
+/// while `f` runs.
+pub fn with_reporter<R>(
+    reporter: impl Fn(&PotentialDeadlock) + Send + Sync + 'static,
+    f: impl FnOnce() -> R,
+) -> R {
+    struct Restore(Option<Reporter>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            LOCAL_REPORTER.with(|local| *local.borrow_mut() = self.0.take());
+        }
+    }
+
+    let previous = LOCAL_REPORTER.with(|local| local.borrow_mut().replace(Arc::new(reporter)));
+    let _restore = Restore(previous);
+    f()
+}
+
+#[cfg(debug_assertions)]
+fn report(deadlock: &PotentialDeadlock) {
+    let reporter = LOCAL_REPORTER
+        .with(|local| local.borrow().clone())
+        .or_else(|| {
+            let global = GLOBAL_REPORTER
+                .read()
+                .unwrap_or_else(|error| error.into_inner());
+            global.clone()
+        });
+    match reporter {
+        Some(reporter) => reporter(deadlock),
+        None => eprintln!("{deadlock}"),
+    }
+}
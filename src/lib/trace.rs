@@ -0,0 +1,200 @@
+//! Runtime support for the `#[trace]` attribute.
+//!
+//! Every traced call produces an [`Event::Enter`] record and then either an
+//! [`Event::Exit`] or, for functions returning an `Err`, an [`Event::Error`].
+//! Records go to the sink installed on the current thread by [`with_sink`],
+//! otherwise to the global one set by [`set_sink`], which defaults to stderr.
+
+use std::cell::RefCell;
+use std::fmt::{self, Debug};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// Logs the arguments, the returned value and the elapsed time of each call.
+///
+/// Arguments and return values must implement `Debug`, the receiver of a
+/// method is not logged.
+///
+/// # Examples
+/// ```
+/// use lib::trace::{trace, with_sink, Event, Recorder};
+///
+/// #[trace]
+/// fn divide(a: i32, b: i32) -> Result<i32, String> {
+///     a.checked_div(b).ok_or_else(|| "division by zero".to_string())
+/// }
+///
+/// let recorder = Recorder::default();
+/// with_sink(recorder.clone(), || divide(1, 0)).unwrap_err();
+/// let records = recorder.records();
+/// assert_eq!(records[0].to_string(), "enter divide(a: 1, b: 0)");
+/// assert!(matches!(&records[1].event, Event::Error { error, .. } if error == "\"division by zero\""));
+/// ```
+pub use local_macro::trace;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub function: &'static str,
+    pub event: Event,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Enter {
+        /// Names and `Debug` representations
+        arguments: Vec<(&'static str, String)>,
+    },
+    Exit {
+        output: String,
+        elapsed: Duration,
+    },
+    Error {
+        error: String,
+        elapsed: Duration,
+    },
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.event {
+            Event::Enter { arguments } => {
+                write!(f, "enter {}(", self.function)?;
+                for (index, (name, value)) in arguments.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{name}: {value}")?;
+                }
+                write!(f, ")")
+            }
+            Event::Exit { output, elapsed } => {
+                write!(f, "exit {} -> {output} [{elapsed:?}]", self.function)
+            }
+            Event::Error { error, elapsed } => {
+                write!(f, "error {} -> Err({error}) [{elapsed:?}]", self.function)
+            }
+        }
+    }
+}
+
+// ____________________________________________________________
+// Sinks
+
+pub trait Sink: Send + Sync {
+    fn record(&self, record: Record);
+}
+
+/// Writes every record on its own line to stderr.
+pub struct Stderr;
+
+impl Sink for Stderr {
+    fn record(&self, record: Record) {
+        eprintln!("{record}");
+    }
+}
+
+/// Keeps the records in memory, clones share the same records.
+#[derive(Clone, Default)]
+pub struct Recorder(Arc<Mutex<Vec<Record>>>);
+
+impl Recorder {
+    pub fn records(&self) -> Vec<Record> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl Sink for Recorder {
+    fn record(&self, record: Record) {
+        self.0.lock().unwrap().push(record);
+    }
+}
+
+static GLOBAL_SINK: RwLock<Option<Arc<dyn Sink>>> = RwLock::new(None);
+
+thread_local! {
+    static LOCAL_SINK: RefCell<Option<Arc<dyn Sink>>> = const { RefCell::new(None) };
+}
+
+/// Replaces the sink used by threads without a local one.
+pub fn set_sink(sink: impl Sink + 'static) {
+    *GLOBAL_SINK.write().unwrap() = Some(Arc::new(sink));
+}
+
+/// Sends the records of the current thread to `sink` while `f` runs.
+pub fn with_sink<R>(sink: impl Sink + 'static, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<Arc<dyn Sink>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            LOCAL_SINK.with(|local| *local.borrow_mut() = self.0.take());
+        }
+    }
+
+    let previous = LOCAL_SINK.with(|local| local.borrow_mut().replace(Arc::new(sink)));
+    let _restore = Restore(previous);
+    f()
+}
+
+fn emit(record: Record) {
+    let sink = LOCAL_SINK
+        .with(|local| local.borrow().clone())
+        .or_else(|| GLOBAL_SINK.read().unwrap().clone());
+    match sink {
+        Some(sink) => sink.record(record),
+        None => Stderr.record(record),
+    }
+}
+
+// ____________________________________________________________
+// Used by the generated code
+
+#[doc(hidden)]
+pub struct Call {
+    function: &'static str,
+    start: Instant,
+}
+
+#[doc(hidden)]
+impl Call {
+    pub fn enter(function: &'static str, arguments: &[(&'static str, &dyn Debug)]) -> Call {
+        let arguments = arguments
+            .iter()
+            .map(|(name, value)| (*name, format!("{value:?}")))
+            .collect();
+        emit(Record {
+            function,
+            event: Event::Enter { arguments },
+        });
+        Call {
+            function,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn exit(self, output: &dyn Debug) {
+        let elapsed = self.start.elapsed();
+        emit(Record {
+            function: self.function,
+            event: Event::Exit {
+                output: format!("{output:?}"),
+                elapsed,
+            },
+        });
+    }
+
+    pub fn exit_result<T: Debug, E: Debug>(self, output: &Result<T, E>) {
+        match output {
+            Ok(_) => self.exit(output),
+            Err(error) => {
+                let elapsed = self.start.elapsed();
+                emit(Record {
+                    function: self.function,
+                    event: Event::Error {
+                        error: format!("{error:?}"),
+                        elapsed,
+                    },
+                });
+            }
+        }
+    }
+}
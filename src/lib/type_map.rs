@@ -0,0 +1,278 @@
+//! A map holding at most one value of each type.
+//!
+//! The value type of the map is a trait object: `dyn Any` for [`TypeMap`],
+//! with `Send + Sync` for [`SyncTypeMap`], and [`CloneAny`] or
+//! [`CloneAnySync`] for the maps implementing `Clone`.
+
+use std::any::{Any, TypeId};
+use std::collections::hash_map::{self, HashMap};
+use std::fmt;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::marker::PhantomData;
+
+/// Values of any type, with `insert`, `get`, `get_mut`, `remove` and `entry`
+/// taking the type as key.
+///
+/// # Examples
+/// ```
+/// use lib::TypeMap;
+///
+/// #[derive(Debug, PartialEq)]
+/// struct RequestId(u64);
+///
+/// let mut extensions = TypeMap::new();
+/// extensions.insert(RequestId(7));
+/// extensions.insert("user");
+/// assert_eq!(extensions.get::<RequestId>(), Some(&RequestId(7)));
+///
+/// *extensions.entry::<u32>().or_default() += 2;
+/// *extensions.entry::<u32>().or_default() += 2;
+/// assert_eq!(extensions.remove::<u32>(), Some(4));
+/// assert_eq!(extensions.insert("admin"), Some("user"));
+/// assert_eq!(extensions.len(), 2);
+/// ```
+pub struct TypeMap<A: ?Sized + Storage = dyn Any> {
+    map: HashMap<TypeId, Box<A>, BuildTypeIdHasher>,
+}
+
+/// A [`TypeMap`] that can be shared between threads.
+pub type SyncTypeMap = TypeMap<dyn Any + Send + Sync>;
+
+/// A [`TypeMap`] of `Clone` values, itself `Clone`.
+pub type CloneTypeMap = TypeMap<dyn CloneAny>;
+
+/// A [`TypeMap`] of `Clone + Send + Sync` values, itself `Clone + Send + Sync`.
+pub type CloneSyncTypeMap = TypeMap<dyn CloneAnySync>;
+
+impl TypeMap {
+    /// As for `HashMap::new`, only for the default parameter, the other maps
+    /// are created with `default()`.
+    pub fn new() -> TypeMap {
+        TypeMap::default()
+    }
+}
+
+impl<A: ?Sized + Storage> TypeMap<A> {
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear()
+    }
+
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Returns the previous value of type `T`.
+    pub fn insert<T: IntoStorage<A>>(&mut self, value: T) -> Option<T> {
+        self.map
+            .insert(TypeId::of::<T>(), value.into_storage())
+            .map(|previous| downcast(previous))
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.map
+            .get(&TypeId::of::<T>())
+            .map(|value| value.as_any().downcast_ref().unwrap())
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.map
+            .get_mut(&TypeId::of::<T>())
+            .map(|value| value.as_any_mut().downcast_mut().unwrap())
+    }
+
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        self.map.remove(&TypeId::of::<T>()).map(downcast)
+    }
+
+    pub fn entry<T: IntoStorage<A>>(&mut self) -> Entry<'_, A, T> {
+        Entry {
+            entry: self.map.entry(TypeId::of::<T>()),
+            value: PhantomData,
+        }
+    }
+}
+
+fn downcast<A: ?Sized + Storage, T: 'static>(value: Box<A>) -> T {
+    *value.into_any().downcast().unwrap()
+}
+
+impl<A: ?Sized + Storage> Default for TypeMap<A> {
+    fn default() -> TypeMap<A> {
+        TypeMap {
+            map: HashMap::default(),
+        }
+    }
+}
+
+impl<A: ?Sized + Storage> Clone for TypeMap<A>
+where
+    Box<A>: Clone,
+{
+    fn clone(&self) -> TypeMap<A> {
+        TypeMap {
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl<A: ?Sized + Storage> fmt::Debug for TypeMap<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TypeMap")
+            .field("len", &self.map.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The value of type `T` in a [`TypeMap`], present or not.
+pub struct Entry<'a, A: ?Sized + Storage, T> {
+    entry: hash_map::Entry<'a, TypeId, Box<A>>,
+    value: PhantomData<T>,
+}
+
+impl<'a, A: ?Sized + Storage, T: IntoStorage<A>> Entry<'a, A, T> {
+    pub fn or_insert(self, default: T) -> &'a mut T {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with(self, default: impl FnOnce() -> T) -> &'a mut T {
+        let value = match self.entry {
+            hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            hash_map::Entry::Vacant(entry) => entry.insert(default().into_storage()),
+        };
+        value.as_any_mut().downcast_mut().unwrap()
+    }
+
+    pub fn or_default(self) -> &'a mut T
+    where
+        T: Default,
+    {
+        self.or_insert_with(T::default)
+    }
+
+    pub fn and_modify(mut self, f: impl FnOnce(&mut T)) -> Entry<'a, A, T> {
+        if let hash_map::Entry::Occupied(entry) = &mut self.entry {
+            f(entry.get_mut().as_any_mut().downcast_mut().unwrap());
+        }
+        self
+    }
+}
+
+// ____________________________________________________________
+// Value types
+
+/// The trait objects a [`TypeMap`] can hold.
+pub trait Storage: 'static {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+/// The values a `TypeMap<A>` can hold.
+pub trait IntoStorage<A: ?Sized>: 'static {
+    fn into_storage(self) -> Box<A>;
+}
+
+/// `Any` for `Clone` types.
+pub trait CloneAny: Any {
+    #[doc(hidden)]
+    fn clone_any(&self) -> Box<dyn CloneAny>;
+}
+
+impl<T: Any + Clone> CloneAny for T {
+    fn clone_any(&self) -> Box<dyn CloneAny> {
+        Box::new(self.clone())
+    }
+}
+
+/// `Any` for `Clone + Send + Sync` types.
+pub trait CloneAnySync: Any + Send + Sync {
+    #[doc(hidden)]
+    fn clone_any_sync(&self) -> Box<dyn CloneAnySync>;
+}
+
+impl<T: Any + Clone + Send + Sync> CloneAnySync for T {
+    fn clone_any_sync(&self) -> Box<dyn CloneAnySync> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn CloneAny> {
+    fn clone(&self) -> Box<dyn CloneAny> {
+        (**self).clone_any()
+    }
+}
+
+impl Clone for Box<dyn CloneAnySync> {
+    fn clone(&self) -> Box<dyn CloneAnySync> {
+        (**self).clone_any_sync()
+    }
+}
+
+macro_rules! impl_storage {
+    ($($object:ty: [$($bound:tt)*],)*) => {
+        $(
+            impl Storage for $object {
+                fn as_any(&self) -> &dyn Any {
+                    self
+                }
+
+                fn as_any_mut(&mut self) -> &mut dyn Any {
+                    self
+                }
+
+                fn into_any(self: Box<Self>) -> Box<dyn Any> {
+                    self
+                }
+            }
+
+            impl<T: Any $($bound)*> IntoStorage<$object> for T {
+                fn into_storage(self) -> Box<$object> {
+                    Box::new(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_storage! {
+    dyn Any: [],
+    dyn Any + Send + Sync: [+ Send + Sync],
+    dyn CloneAny: [+ Clone],
+    dyn CloneAnySync: [+ Clone + Send + Sync],
+}
+
+// ____________________________________________________________
+// Hashing
+
+/// A hasher for `TypeId`s only, which are already hashes.
+///
+/// `TypeId` hashes itself with a single `write_u64`, kept as the hash.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TypeIdHasher(u64);
+
+pub type BuildTypeIdHasher = BuildHasherDefault<TypeIdHasher>;
+
+impl Hasher for TypeIdHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.0 = value;
+    }
+
+    /// Only in case `TypeId` hashes differently some day.
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = self.0.rotate_left(8) ^ u64::from(byte);
+        }
+    }
+}
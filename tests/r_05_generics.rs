@@ -287,3 +287,39 @@ fn phantom_data() {
 
     // let x = one_mile + one_meter; // Error
 }
+
+// Generated by a macro, src/lib/quantity.rs
+mod units {
+    lib::quantity!(pub Length: Meter("m") = 1.0, Mile("mi") = 1609.344, Kilometer("km") = 1000.0);
+    lib::quantity!(pub Duration: Second("s") = 1.0, Hour("h") = 3600.0);
+}
+
+#[test]
+fn phantom_data_macro() {
+    use units::*;
+
+    let one_meter = Length::<Meter>::new(1.0);
+    let one_mile = Length::<Mile>::new(1.0);
+
+    let two_miles = one_mile + one_mile;
+    assert_eq!(two_miles.value(), 2.0);
+    assert_eq!((two_miles - one_mile) * 3.0, 3.0 * one_mile);
+    assert_eq!((two_miles / 4.0).to_string(), "0.5 mi");
+    assert_eq!((-one_meter).to_string(), "-1 m");
+
+    // let x = one_mile + one_meter; // Error
+    let x = one_mile.convert::<Meter>() + one_meter;
+    assert_eq!(x.value(), 1610.344);
+    assert_eq!(format!("{:.2}", x.convert::<Kilometer>()), "1.61 km");
+    assert_eq!(one_mile.convert::<Kilometer>().convert::<Mile>(), one_mile);
+
+    assert_eq!(
+        Length::<Kilometer>::new(1.0),
+        Length::<Meter>::new(1000.0).convert()
+    );
+    assert!(Length::<Kilometer>::new(1.0) < Length::<Mile>::new(1.0).convert());
+    assert_eq!(
+        Duration::<Hour>::new(0.5).convert::<Second>().value(),
+        1800.0
+    );
+}
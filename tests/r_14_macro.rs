@@ -87,12 +87,353 @@ fn test_macro() {
     }
 }
 
+// Exported declarative macros
+// src/lib/macros.rs
+
+lib::create_function! {
+    fn(x: f64) -> f64 {
+        half => x / 2.0,
+        twice => x * 2.0,
+    }
+}
+
+#[test]
+fn exported_macros() {
+    assert_eq!(
+        lib::min!(5u32, 2u32 * 3, 4u32),
+        find_min!(5u32, 2u32 * 3, 4u32)
+    );
+    let min: i64 = lib::min!(7u32, -1i32, 3u8);
+    assert_eq!(min, -1);
+    let max: f64 = lib::max!(1u8, 2.5f32, -3i32);
+    assert_eq!(max, 2.5);
+    assert_eq!(lib::sum!(1, 2, 3), 6);
+    assert_eq!(lib::sum!(0.5, 1u8), 1.5);
+
+    let (a, b, c) = lib::calculate! {
+        eval 1 + 2,
+        eval 3 + 4,
+        eval (2 * 3) + 1
+    };
+    assert_eq!((a, b, c), (3, 7, 7));
+
+    assert_eq!(half(3.0), 1.5);
+    assert_eq!(twice(3.0), 6.0);
+}
+
+// src/lib/state_machine.rs
+
+#[derive(Default)]
+struct Turnstile {
+    coins: u32,
+    log: Vec<&'static str>,
+}
+
+fn has_coins(turnstile: &Turnstile) -> bool {
+    turnstile.coins > 0
+}
+
+lib::state_machine! {
+    mod turnstile {
+        context: Turnstile,
+        initial: Locked,
+        states: { Locked, Unlocked },
+        events: { Coin, Push },
+        transitions: {
+            Locked + Coin => Unlocked,
+            Locked + Push if has_coins => Unlocked,
+            Unlocked + Push => Locked,
+            Unlocked + Coin => Unlocked,
+        },
+        enter: {
+            Unlocked => |t| t.log.push("enter Unlocked"),
+            Locked => |t| t.log.push("enter Locked"),
+        },
+        exit: { Unlocked => |t| t.log.push("exit Unlocked") },
+    }
+}
+
+#[test]
+fn state_machine_macro() {
+    use turnstile::{Event, InvalidTransition, Machine, State};
+
+    let mut machine = Machine::new(Turnstile::default());
+    assert_eq!(machine.state(), Machine::INITIAL);
+    assert_eq!(
+        machine.transition(Event::Push),
+        Err(InvalidTransition {
+            state: State::Locked,
+            event: Event::Push,
+        })
+    );
+    machine.context_mut().coins = 1;
+    assert_eq!(machine.transition(Event::Push), Ok(State::Unlocked));
+    assert_eq!(machine.transition(Event::Coin), Ok(State::Unlocked));
+    assert_eq!(machine.transition(Event::Push), Ok(State::Locked));
+    assert_eq!(
+        machine.into_context().log,
+        [
+            "enter Unlocked",
+            "exit Unlocked",
+            "enter Unlocked",
+            "exit Unlocked",
+            "enter Locked"
+        ]
+    );
+
+    let dot = Machine::dot();
+    assert!(dot.starts_with("digraph turnstile {\n    __start [shape=point];\n"));
+    assert!(dot.contains("    __start -> Locked;\n"));
+    assert!(dot.contains("    Locked -> Unlocked [label=\"Push [has_coins]\"];\n"));
+    assert!(dot.ends_with("}\n"));
+}
+
 // Procedural Macros
 
 // 1. Custom #[derive] macros
-#[derive(Debug)]
+// local-macro/src/describe.rs
+use lib::describe::{Describe, TypeKind};
+
+#[derive(Debug, Describe)]
 struct Structure {
     name: String,
 }
+
+#[derive(Describe)]
+#[repr(C)]
+struct Generic<T> {
+    a: u8,
+    b: T,
+}
+
+#[derive(Describe)]
+struct Tuple(u8, u64);
+
+#[derive(Describe)]
+enum Enumeration {
+    A,
+    B(i32),
+    C { x: f64 },
+}
+
+#[test]
+fn derive_macro() {
+    let description = Structure::describe();
+    assert_eq!(description.name, std::any::type_name::<Structure>());
+    assert_eq!(description.kind, TypeKind::Struct);
+    assert_eq!(description.size, std::mem::size_of::<Structure>());
+    assert_eq!(description.fields.len(), 1);
+    assert_eq!(description.fields[0].name, "name");
+    assert_eq!(
+        description.fields[0].type_name,
+        std::any::type_name::<String>()
+    );
+
+    let description = Generic::<u32>::describe();
+    assert_eq!(description.align, 4);
+    assert_eq!(description.field("a").unwrap().offset, Some(0));
+    assert_eq!(description.field("b").unwrap().offset, Some(4));
+    assert_eq!(description.field("b").unwrap().type_name, "u32");
+
+    let description = Tuple::describe();
+    let names: Vec<_> = description.fields.iter().map(|f| f.name).collect();
+    assert_eq!(names, ["0", "1"]);
+
+    let description = Enumeration::describe();
+    assert_eq!(description.kind, TypeKind::Enum);
+    assert!(description.fields.is_empty());
+    let names: Vec<_> = description.variants.iter().map(|v| v.name).collect();
+    assert_eq!(names, ["A", "B", "C"]);
+    // Laid out by the compiler, with data
+    assert_eq!(description.tag, None);
+    let x = &description.variant("C").unwrap().fields[0];
+    assert_eq!((x.name, x.type_name, x.offset), ("x", "f64", None));
+}
+
 // 2. Attribute-like macros
+// local-macro/src/trace.rs
+use lib::trace::{trace, with_sink, Event, Recorder};
+
+#[trace]
+fn bar(x: &mut i32) {
+    *x = 20;
+}
+
+#[trace]
+fn largest<T: PartialOrd + std::fmt::Debug + Copy>(values: &[T]) -> Option<T> {
+    let mut largest = *values.first()?;
+    for &value in values {
+        if value > largest {
+            largest = value;
+        }
+    }
+    Some(largest)
+}
+
+#[trace]
+fn parse(text: &str) -> Result<i32, std::num::ParseIntError> {
+    let value = text.parse::<i32>()?;
+    Ok(value * 2)
+}
+
+#[trace]
+fn evens(limit: u32) -> impl Iterator<Item = u32> + std::fmt::Debug {
+    (0..limit).step_by(2)
+}
+
+#[test]
+fn attribute_macro() {
+    let recorder = Recorder::default();
+    let mut x = 10;
+    with_sink(recorder.clone(), || bar(&mut x));
+    assert_eq!(x, 20);
+    let records = recorder.records();
+    assert_eq!(records[0].to_string(), "enter bar(x: 10)");
+    assert!(records[1].to_string().starts_with("exit bar -> () ["));
+
+    let recorder = Recorder::default();
+    with_sink(recorder.clone(), || {
+        assert_eq!(largest(&[1.5, 3.0, 2.0]), Some(3.0));
+        assert_eq!(largest::<u8>(&[]), None);
+        assert_eq!(parse("21"), Ok(42));
+        assert!(parse("x").is_err());
+        assert_eq!(evens(5).collect::<Vec<_>>(), [0, 2, 4]);
+    });
+    let records = recorder.records();
+    assert_eq!(records.len(), 10);
+    assert_eq!(
+        records[0].to_string(),
+        "enter largest(values: [1.5, 3.0, 2.0])"
+    );
+    assert!(matches!(&records[1].event, Event::Exit { output, .. } if output == "Some(3.0)"));
+    assert!(matches!(&records[3].event, Event::Exit { output, .. } if output == "None"));
+    assert!(matches!(&records[5].event, Event::Exit { output, .. } if output == "Ok(42)"));
+    assert_eq!(records[7].function, "parse");
+    assert!(
+        matches!(&records[7].event, Event::Error { error, .. } if error.contains("InvalidDigit"))
+    );
+    assert!(matches!(&records[9].event, Event::Exit { output, .. } if output.contains("StepBy")));
+}
 // 3. Function-like macros
+// local-macro/src/const_eval.rs
+
+macro_rules! seconds {
+    ($hours:expr) => {
+        lib::const_eval!($hours * 60 * 60)
+    };
+}
+
+#[test]
+fn function_like_macro() {
+    const BUFFER_SIZE: usize = lib::const_eval!(4 * 1_024);
+    assert_eq!(BUFFER_SIZE, 4096);
+
+    let x: i8 = lib::const_eval!(-(2 + 3) * 2);
+    assert_eq!(x, -10);
+    assert_eq!(lib::const_eval!(17 / 5 % 2), 1);
+    assert_eq!(seconds!(1 + 1), 7200);
+
+    let min: i64 = lib::const_eval!(-9_223_372_036_854_775_807 - 1);
+    assert_eq!(min, i64::MIN);
+}
+
+// __________________________________________
+// Diagnostics
+
+/// Builds the library as for this test, with the same features and
+/// profile, returning the path of its `rlib`.
+fn build_library() -> std::path::PathBuf {
+    let mut command = std::process::Command::new(std::env::var("CARGO").unwrap_or("cargo".into()));
+    command
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["build", "--lib", "--quiet", "--message-format=json"])
+        // Not to wait for the lock held by `cargo test`
+        .arg("--target-dir")
+        .arg(format!("{}/ui", env!("CARGO_TARGET_TMPDIR")));
+    if cfg!(feature = "foo") {
+        command.arg("--features=foo");
+    }
+    if !cfg!(debug_assertions) {
+        command.arg("--release");
+    }
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    // The artifact of the library lists its files, the `rlib` among them
+    let messages = String::from_utf8(output.stdout).unwrap();
+    let artifact = messages
+        .lines()
+        .find(|line| {
+            line.contains(r#""reason":"compiler-artifact""#)
+                && line.contains(r#""name":"lib","src_path""#)
+        })
+        .unwrap();
+    let end = artifact.find(r#".rlib""#).unwrap() + ".rlib".len();
+    let start = artifact[..end].rfind('"').unwrap() + 1;
+    artifact[start..end].into()
+}
+
+/// Compiles `tests/ui/{name}.rs` against `library`, returning the headers
+/// of the errors and their primary locations, which don't depend on the
+/// rendering of rustc nor on the sources of the library.
+fn compile_errors(library: &std::path::Path, name: &str) -> String {
+    // Next to the `rlib`, uplifted from there
+    let deps = library.parent().unwrap().join("deps");
+    let output = std::process::Command::new(std::env::var("RUSTC").unwrap_or("rustc".into()))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args([
+            "--edition",
+            "2021",
+            "--color",
+            "never",
+            "--emit",
+            "metadata",
+        ])
+        .arg("--out-dir")
+        .arg(std::env::temp_dir())
+        .arg("--extern")
+        .arg(format!("lib={}", library.display()))
+        .arg("-L")
+        .arg(format!("dependency={}", deps.display()))
+        .arg(format!("tests/ui/{name}.rs"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "{name} compiled");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let mut errors = String::new();
+    let mut located = true;
+    for line in stderr.lines() {
+        let line = line.trim_start();
+        if line.starts_with("error") && !line.starts_with("error: aborting") {
+            errors += &format!("{line}\n");
+            located = false;
+        } else if line.starts_with("--> ") && !located {
+            errors += &format!("{line}\n");
+            located = true;
+        }
+    }
+    errors
+}
+
+#[test]
+fn macro_diagnostics() {
+    let library = build_library();
+    // `UPDATE_UI=1` writes the errors as the expected ones
+    for name in [
+        "calculate_without_eval",
+        "min_without_arguments",
+        "min_without_common_type",
+        "max_without_common_type",
+        "sum_without_common_type",
+    ] {
+        let errors = compile_errors(&library, name);
+        let expected = format!("{}/tests/ui/{name}.stderr", env!("CARGO_MANIFEST_DIR"));
+        if std::env::var_os("UPDATE_UI").is_some() {
+            std::fs::write(&expected, &errors).unwrap();
+        }
+        assert_eq!(
+            errors,
+            std::fs::read_to_string(&expected).unwrap(),
+            "{name}"
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use std::panic::catch_unwind;
+use std::sync::Mutex;
 
 // The type representation is the representation of its only field
 #[repr(transparent)]
@@ -9,6 +10,7 @@ mod clib {
     pub enum NLOpaqueType {}
 
     #[repr(C)]
+    #[derive(lib::describe::Describe)]
     pub struct NLValue {
         pub integer: i32,
         pub boolean: bool,
@@ -89,3 +91,185 @@ fn test_clib() {
         assert_eq!(vector, [0, 1, 2]);
     }
 }
+
+/// Held by the tests creating values, `live_allocations` counting the
+/// values of the whole process.
+static VALUES: Mutex<()> = Mutex::new(());
+
+#[test]
+fn variant_round_trip() {
+    use lib::clib::{live_allocations, Value, ValueKind};
+
+    let _values = VALUES.lock().unwrap();
+    let before = live_allocations();
+    {
+        assert_eq!(i32::try_from(Value::from(-7)), Ok(-7));
+        assert_eq!(bool::try_from(Value::from(true)), Ok(true));
+        assert_eq!(f64::try_from(Value::from(1.5)), Ok(1.5));
+
+        let string = String::from("héllo\0wörld");
+        let value = Value::from(string.clone());
+        assert_eq!(live_allocations() - before, 1);
+        assert_eq!(String::try_from(&value), Ok(string));
+        assert_eq!(String::try_from(Value::from("")), Ok(String::new()));
+
+        let vector = vec![i64::MIN, -1, 0, i64::MAX];
+        let value = Value::from(vector.clone());
+        assert_eq!(value.clone(), value);
+        assert_eq!(Vec::<i64>::try_from(&value), Ok(vector));
+        assert_eq!(Vec::<i64>::try_from(Value::from(Vec::new())), Ok(vec![]));
+        assert_eq!(Vec::<i64>::try_from(Value::sequence(3)), Ok(vec![0, 1, 2]));
+
+        let error = i32::try_from(&value).unwrap_err();
+        assert_eq!(error.expected, ValueKind::Int32);
+        assert_eq!(error.found, ValueKind::Int64Array);
+    }
+    assert_eq!(live_allocations(), before);
+}
+
+#[test]
+fn fill_strategies() {
+    use lib::clib::{fill, fill_vec, Constant, Generate, Range};
+    use std::mem::MaybeUninit;
+
+    assert_eq!(fill_vec(0, Range::default()), []);
+    let range = Range {
+        start: i64::MAX,
+        step: 1,
+    };
+    assert_eq!(fill_vec(2, range), [i64::MAX, i64::MIN]);
+    assert_eq!(fill_vec(2, Constant(-1)), [-1, -1]);
+
+    let mut calls = Vec::new();
+    let vector = fill_vec(
+        4,
+        Generate(|i| {
+            calls.push(i);
+            10 * i as i64
+        }),
+    );
+    assert_eq!(vector, [0, 10, 20, 30]);
+    assert_eq!(calls, [0, 1, 2, 3]);
+
+    let mut buffer = [MaybeUninit::uninit(); 4];
+    let slice = fill(&mut buffer[1..3], Constant(5));
+    slice[0] += 1;
+    assert_eq!(slice, [6, 5]);
+
+    let result = catch_unwind(|| fill_vec(3, Generate(|_| panic!("Oops!"))));
+    assert!(result.is_err());
+}
+
+// Layouts compared with the ones compiled by build.rs, src/lib/layout.rs
+
+#[test]
+fn layouts() {
+    use lib::describe::Describe;
+    use lib::layout::{Layout, Mismatch};
+
+    let c_layouts = lib::clib::c_layouts();
+    let c_value = c_layouts.iter().find(|l| l.name == "NLValue").unwrap();
+    let value = Layout::of::<clib::NLValue>();
+    assert_eq!(value.compare(c_value), []);
+    assert_eq!(value.padding(), vec![(5..8)]);
+    assert!(value.fields[1].has_niche && !value.fields[0].has_niche);
+    assert_eq!(value.niche, vec![(4..5)]);
+
+    #[derive(Describe)]
+    #[repr(C, packed)]
+    struct PackedValue {
+        integer: i32,
+        boolean: bool,
+    }
+    let packed = Layout::of::<PackedValue>();
+    assert_eq!(
+        packed.compare(c_value),
+        [Mismatch::Size(5, 8), Mismatch::Align(1, 4)]
+    );
+    assert_eq!(
+        packed.to_string(),
+        format!(
+            "{}: size 5, align 1, has a niche in bytes 4..5\n     0 | a | a | a | a | B |\n     a  integer: i32, offset 0, size 4\n     b  boolean: bool, offset 4, size 1, has a niche in bytes 4..5",
+            std::any::type_name::<PackedValue>()
+        )
+    );
+
+    // Niches located in the scalars and in the fields of described types
+    #[derive(Describe)]
+    #[repr(C)]
+    struct Nested {
+        count: u16,
+        value: clib::NLValue,
+        letter: char,
+        reference: &'static u8,
+        name: String,
+    }
+    let nested = Layout::of::<Nested>();
+    assert_eq!(nested.niche, vec![8..9, 12..16, 16..24]);
+    assert!(nested.fields[4].has_niche && nested.fields[4].niche.is_empty());
+    let diagram = nested.to_string();
+    assert!(diagram.contains("     8 | B b b b C C C C |"), "{diagram}");
+    assert!(
+        diagram.contains("has a niche in unknown bytes"),
+        "{diagram}"
+    );
+
+    #[derive(Describe)]
+    #[repr(u8)]
+    enum Tagged {
+        Empty,
+        Byte(u8),
+        Word(u32),
+    }
+    let tagged = Layout::of::<Tagged>();
+    assert_eq!(tagged.tag, Some(0..1));
+    assert_eq!(tagged.niche, vec![(0..1)]);
+    assert_eq!(
+        tagged.to_string().lines().skip(1).collect::<Vec<_>>(),
+        [
+            "     0 | T ? ? ? | ? ? ? ? |",
+            "     t  tag, offset 0, size 1",
+            "     ?  variant fields"
+        ]
+    );
+
+    #[derive(Describe)]
+    #[repr(C)]
+    struct Swapped {
+        boolean: bool,
+        integer: i32,
+    }
+    assert_eq!(
+        Layout::of::<Swapped>().compare(c_value),
+        [
+            Mismatch::FieldSize {
+                field: "boolean",
+                sizes: (1, 4)
+            },
+            Mismatch::FieldSize {
+                field: "integer",
+                sizes: (4, 1)
+            }
+        ]
+    );
+
+    #[derive(Describe)]
+    #[repr(transparent)]
+    struct Transparent(f64);
+    let transparent = Layout::of::<Transparent>();
+    assert_eq!((transparent.size, transparent.align), (8, 8));
+    assert_eq!(transparent.padding(), []);
+
+    #[derive(Describe)]
+    #[repr(align(16))]
+    struct Aligned(u8);
+    let aligned = Layout::of::<Aligned>();
+    assert_eq!(aligned.padding(), vec![(1..16)]);
+    assert!(aligned
+        .to_string()
+        .contains("| a . . . . . . . . . . . . . . . |"));
+
+    for (rust, c) in lib::clib::mirrored_layouts() {
+        assert_eq!(rust.compare(&c), [], "{rust}\n{c}");
+    }
+}
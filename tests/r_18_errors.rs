@@ -117,6 +117,150 @@ fn custom_error() {
     }
 }
 
+// Report of the whole chain, src/lib/report.rs
+
+#[derive(Debug)]
+struct ApplicationError(HighLevelError);
+
+impl Error for ApplicationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+impl fmt::Display for ApplicationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ApplicationError\nwith \"details\"")
+    }
+}
+
+#[test]
+fn report() {
+    use lib::report::Report;
+
+    let error = ApplicationError(HighLevelError::from(LowLevelError));
+    let report = Report::new(error);
+    let messages: Vec<_> = report.chain().map(|e| e.to_string()).collect();
+    assert_eq!(
+        messages,
+        [
+            "ApplicationError\nwith \"details\"",
+            "HighLevelError typeA",
+            "LowLevelError"
+        ]
+    );
+    assert!(report.backtrace().is_none());
+
+    assert_eq!(
+        report.compact().to_string(),
+        "ApplicationError with \"details\": HighLevelError typeA: LowLevelError"
+    );
+    assert_eq!(report.to_string(), report.compact().to_string());
+    assert_eq!(
+        format!("{report:#}"),
+        "Error: ApplicationError\n       with \"details\"\n\nCaused by:\n    1: HighLevelError typeA\n    2: LowLevelError"
+    );
+    assert_eq!(
+        report.json().to_string(),
+        r#"{"error":"ApplicationError\nwith \"details\"","causes":["HighLevelError typeA","LowLevelError"],"backtrace":null}"#
+    );
+
+    let report = Report::from(HighLevelError::TypeB);
+    assert_eq!(format!("{report:?}"), "Error: HighLevelError typeB");
+}
+
+// Derived, src/lib/error.rs
+
+#[derive(Debug, lib::error::Error)]
+enum DerivedError {
+    #[error("HighLevelError typeA")]
+    TypeA(#[from] LowLevelError),
+    #[error("HighLevelError typeB")]
+    TypeB,
+    #[error("invalid {name}: {value:?}")]
+    Invalid { name: &'static str, value: i32 },
+    #[error("{0} failed")]
+    Boxed(&'static str, #[source] Box<dyn Error + Send + Sync>),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Debug, lib::error::Error)]
+#[error("application error {{{code}}}")]
+struct DerivedApplicationError {
+    code: u8,
+    source: DerivedError,
+}
+
+#[test]
+fn derive_error() {
+    let error = DerivedError::from(LowLevelError);
+    assert_eq!(error.to_string(), "HighLevelError typeA");
+    assert_eq!(error.source().unwrap().to_string(), "LowLevelError");
+    assert!(DerivedError::TypeB.source().is_none());
+
+    let error = DerivedError::Invalid {
+        name: "port",
+        value: -1,
+    };
+    assert_eq!(error.to_string(), "invalid port: -1");
+
+    let error = DerivedError::Boxed("parsing", "not a number".into());
+    assert_eq!(error.to_string(), "parsing failed");
+    assert_eq!(error.source().unwrap().to_string(), "not a number");
+
+    let inner = std::io::Error::other(LowLevelError);
+    let error = DerivedError::from(inner);
+    assert_eq!(error.to_string(), "LowLevelError");
+    assert!(error.source().is_none());
+
+    let error = DerivedApplicationError {
+        code: 2,
+        source: DerivedError::TypeA(LowLevelError),
+    };
+    assert_eq!(
+        lib::report::Report::new(error).to_string(),
+        "application error {2}: HighLevelError typeA: LowLevelError"
+    );
+}
+
+// Context instead of map_err, keeping the cause
+
+#[test]
+fn context() {
+    use lib::error::{AnyError, Context, ContextError};
+
+    fn get_high_level_error() -> Result<i32, HighLevelError> {
+        Err(HighLevelError::from(LowLevelError))
+    }
+
+    fn foo() -> Result<i32, AnyError> {
+        let x = get_high_level_error().context("Error")?;
+        Ok(x + 1)
+    }
+
+    let error = foo().unwrap_err();
+    let messages: Vec<_> = error.chain().map(|e| e.to_string()).collect();
+    assert_eq!(messages, ["Error", "HighLevelError typeA", "LowLevelError"]);
+    assert!(error.find::<LowLevelError>().is_some());
+    assert!(error.downcast_ref::<HighLevelError>().is_none());
+
+    let error = error
+        .downcast::<ContextError<&str, HighLevelError>>()
+        .unwrap();
+    assert!(matches!(error.into_error(), Some(HighLevelError::TypeA(_))));
+
+    let error = None::<i32>
+        .with_context(|| format!("no {}", "value"))
+        .unwrap_err();
+    assert_eq!(error.to_string(), "no value");
+    assert!(error.source().is_none());
+
+    let error = AnyError::from(error);
+    let report = lib::report::Report::new(error);
+    assert_eq!(report.to_string(), "no value");
+}
+
 // Custom exit codes from main
 
 #[repr(u8)]
@@ -136,6 +280,37 @@ fn main() -> CustomResult {
     CustomResult::A
 }
 
+// Registered exit codes, src/lib/exit.rs
+
+#[test]
+fn exit_codes() {
+    use lib::exit::{self, code, Exit};
+
+    assert_eq!(exit::register::<HighLevelError>(code::SOFTWARE), None);
+    assert_eq!(exit::register::<LowLevelError>(code::DATAERR), None);
+
+    // The most specific code is the one of the cause
+    let error = ApplicationError(HighLevelError::from(LowLevelError));
+    assert_eq!(exit::code_of(&error), Some(code::DATAERR));
+    assert_eq!(exit::code_of(&HighLevelError::TypeB), Some(code::SOFTWARE));
+    assert_eq!(
+        exit::code_of(&ApplicationError(HighLevelError::TypeB)),
+        Some(code::SOFTWARE)
+    );
+
+    let exit: Exit<(), _> = Exit(Err(error));
+    assert_eq!(exit.report(), ExitCode::from(code::DATAERR));
+    assert_eq!(
+        Exit::<(), LowLevelError>(Ok(())).report(),
+        ExitCode::SUCCESS
+    );
+
+    assert_eq!(exit::unregister::<LowLevelError>(), Some(code::DATAERR));
+    assert_eq!(exit::unregister::<HighLevelError>(), Some(code::SOFTWARE));
+    let exit: Exit<(), _> = Exit(Err(LowLevelError));
+    assert_eq!(exit.report(), ExitCode::FAILURE);
+}
+
 // Result implements Termination
 // Ok translated to a C EXIT_SUCCESS and Err to EXIT_FAILURE
 
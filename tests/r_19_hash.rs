@@ -1,8 +1,20 @@
+use lib::hash::analysis::{self, KeySet, Options};
+use lib::hash::sketch::{
+    BloomFilter, CountMinSketch, CountingBloomFilter, DecodeError, HyperLogLog, MergeError,
+};
+use lib::hash::{
+    self, BuildFnv1a64, BuildSipHasher24, BuildXxHash64, Fnv1a32, Fnv1a64, Ring, SipHasher13,
+    SipHasher24, StableHash, XxHash64,
+};
 use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
+use std::time::Duration;
 
 // __________________________________________
 
+#[derive(StableHash)]
 struct Structure {
     x: i32,
 }
@@ -55,3 +67,527 @@ fn custom_hash() {
     let hash = compute_hash(&value, &mut hasher);
     println!("{hash}");
 }
+
+// __________________________________________
+// Hashers of lib::hash, checked against published test vectors
+
+fn hash_bytes<H: Hasher>(mut hasher: H, bytes: &[u8]) -> u64 {
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+/// Writes `bytes` one at a time, then in pieces of 3
+fn assert_incremental<H: Hasher + Clone>(hasher: H, bytes: &[u8]) {
+    let expected = hash_bytes(hasher.clone(), bytes);
+    let mut bytewise = hasher.clone();
+    for byte in bytes {
+        bytewise.write(std::slice::from_ref(byte));
+    }
+    assert_eq!(bytewise.finish(), expected);
+    let mut chunked = hasher;
+    for chunk in bytes.chunks(3) {
+        chunked.write(chunk);
+    }
+    assert_eq!(chunked.finish(), expected);
+}
+
+#[test]
+fn fnv1a() {
+    for (input, hash32, hash64) in [
+        (&b""[..], 0x811c9dc5, 0xcbf29ce484222325),
+        (b"a", 0xe40c292c, 0xaf63dc4c8601ec8c),
+        (b"foobar", 0xbf9cf968, 0x85944171f73967e8),
+    ] {
+        assert_eq!(hash_bytes(Fnv1a32::new(), input), hash32);
+        assert_eq!(hash_bytes(Fnv1a64::new(), input), hash64);
+    }
+    let mut hasher = Fnv1a32::new();
+    hasher.write(b"foobar");
+    assert_eq!(hasher.finish_u32(), 0xbf9cf968);
+}
+
+// The reference vectors of SipHash: key 00 01 .. 0f, message 00 01 .. of
+// the length of the index, hash in little-endian
+const SIP13: [[u8; 8]; 16] = [
+    [0xdc, 0xc4, 0x0f, 0x05, 0x58, 0x01, 0xac, 0xab],
+    [0x93, 0xca, 0x57, 0x7d, 0xf3, 0x9b, 0xf4, 0xc9],
+    [0x4d, 0xd4, 0xc7, 0x4d, 0x02, 0x9b, 0xcb, 0x82],
+    [0xfb, 0xf7, 0xdd, 0xe7, 0xb8, 0x0a, 0xf8, 0x8b],
+    [0x28, 0x83, 0xd3, 0x88, 0x60, 0x57, 0x75, 0xcf],
+    [0x67, 0x3b, 0x53, 0x49, 0x2f, 0xd5, 0xf9, 0xde],
+    [0xa7, 0x22, 0x9f, 0xc5, 0x50, 0x2b, 0x0d, 0xc5],
+    [0x40, 0x11, 0xb1, 0x9b, 0x98, 0x7d, 0x92, 0xd3],
+    [0x8e, 0x9a, 0x29, 0x8d, 0x11, 0x95, 0x90, 0x36],
+    [0xe4, 0x3d, 0x06, 0x6c, 0xb3, 0x8e, 0xa4, 0x25],
+    [0x7f, 0x09, 0xff, 0x92, 0xee, 0x85, 0xde, 0x79],
+    [0x52, 0xc3, 0x4d, 0xf9, 0xc1, 0x18, 0xc1, 0x70],
+    [0xa2, 0xd9, 0xb4, 0x57, 0xb1, 0x84, 0xa3, 0x78],
+    [0xa7, 0xff, 0x29, 0x12, 0x0c, 0x76, 0x6f, 0x30],
+    [0x34, 0x5d, 0xf9, 0xc0, 0x11, 0xa1, 0x5a, 0x60],
+    [0x56, 0x99, 0x51, 0x2a, 0x6d, 0xd8, 0x20, 0xd3],
+];
+
+const SIP24: [[u8; 8]; 16] = [
+    [0x31, 0x0e, 0x0e, 0xdd, 0x47, 0xdb, 0x6f, 0x72],
+    [0xfd, 0x67, 0xdc, 0x93, 0xc5, 0x39, 0xf8, 0x74],
+    [0x5a, 0x4f, 0xa9, 0xd9, 0x09, 0x80, 0x6c, 0x0d],
+    [0x2d, 0x7e, 0xfb, 0xd7, 0x96, 0x66, 0x67, 0x85],
+    [0xb7, 0x87, 0x71, 0x27, 0xe0, 0x94, 0x27, 0xcf],
+    [0x8d, 0xa6, 0x99, 0xcd, 0x64, 0x55, 0x76, 0x18],
+    [0xce, 0xe3, 0xfe, 0x58, 0x6e, 0x46, 0xc9, 0xcb],
+    [0x37, 0xd1, 0x01, 0x8b, 0xf5, 0x00, 0x02, 0xab],
+    [0x62, 0x24, 0x93, 0x9a, 0x79, 0xf5, 0xf5, 0x93],
+    [0xb0, 0xe4, 0xa9, 0x0b, 0xdf, 0x82, 0x00, 0x9e],
+    [0xf3, 0xb9, 0xdd, 0x94, 0xc5, 0xbb, 0x5d, 0x7a],
+    [0xa7, 0xad, 0x6b, 0x22, 0x46, 0x2f, 0xb3, 0xf4],
+    [0xfb, 0xe5, 0x0e, 0x86, 0xbc, 0x8f, 0x1e, 0x75],
+    [0x90, 0x3d, 0x84, 0xc0, 0x27, 0x56, 0xea, 0x14],
+    [0xee, 0xf2, 0x7a, 0x8e, 0x90, 0xca, 0x23, 0xf7],
+    [0xe5, 0x45, 0xbe, 0x49, 0x61, 0xca, 0x29, 0xa1],
+];
+
+#[test]
+fn siphash() {
+    let (k0, k1) = (0x0706050403020100, 0x0f0e0d0c0b0a0908);
+    let message: Vec<u8> = (0..16).collect();
+    for (length, (sip13, sip24)) in SIP13.iter().zip(&SIP24).enumerate() {
+        let input = &message[..length];
+        let hasher = SipHasher13::new_with_keys(k0, k1);
+        assert_eq!(hash_bytes(hasher, input), u64::from_le_bytes(*sip13));
+        let hasher = SipHasher24::new_with_keys(k0, k1);
+        assert_eq!(hash_bytes(hasher, input), u64::from_le_bytes(*sip24));
+    }
+    assert_incremental(SipHasher13::new_with_keys(k0, k1), &message);
+    assert_incremental(SipHasher24::new_with_keys(k0, k1), &message);
+}
+
+#[test]
+fn xxhash64() {
+    let hundred: Vec<u8> = (0..100).collect();
+    for (seed, input, hash) in [
+        (0, &[][..], 0xef46db3751d8e999),
+        (0, &[42], 0x0a9edecebeb03ae4),
+        (0, b"Hello, world!\0", 0x7b06c531ea43e89f),
+        (0, &hundred, 0x6ac1e58032166597),
+        (0xae0543311b702d91, &[], 0x4b6a04fcdf7a4672),
+        (0xae0543311b702d91, &hundred, 0x567e355e0682e1f1),
+    ] {
+        assert_eq!(hash_bytes(XxHash64::with_seed(seed), input), hash);
+    }
+    assert_incremental(XxHash64::with_seed(0), &hundred);
+}
+
+#[test]
+fn build_hashers() {
+    let mut fnv = HashMap::with_hasher(BuildFnv1a64::default());
+    let mut sip = HashMap::with_hasher(BuildSipHasher24::new(1, 2));
+    let mut xx = HashMap::with_hasher(BuildXxHash64::with_seed(3));
+    for x in 0..100 {
+        fnv.insert(x, x);
+        sip.insert(x, x);
+        xx.insert(x, x);
+    }
+    assert!((0..100).all(|x| fnv[&x] == x && sip[&x] == x && xx[&x] == x));
+
+    // Same keys, same hashes
+    let build = BuildSipHasher24::new(1, 2);
+    assert_eq!(
+        compute_hash(&Structure { x: 10 }, &mut build.build_hasher()),
+        compute_hash(&Structure { x: 10 }, &mut build.build_hasher())
+    );
+}
+
+// __________________________________________
+// Stable hashing
+
+/// Keeps the bytes written, to check the encoding itself
+#[derive(Default)]
+struct Recorder(Vec<u8>);
+
+impl Hasher for Recorder {
+    fn finish(&self) -> u64 {
+        0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+}
+
+fn encode<T: StableHash + ?Sized>(value: &T) -> Vec<u8> {
+    let mut recorder = Recorder::default();
+    value.stable_hash(&mut recorder);
+    recorder.0
+}
+
+#[derive(StableHash)]
+struct Pair<T>(T, T);
+
+#[derive(StableHash)]
+enum Shape {
+    Empty,
+    Circle { radius: u32 },
+    Line(Pair<i16>),
+}
+
+#[test]
+fn stable_encoding() {
+    assert_eq!(encode(&Structure { x: 10 }), [10, 0, 0, 0]);
+    assert_eq!(encode(&0x0102usize), [2, 1, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(encode(&(true, 'a')), [1, 0x61, 0, 0, 0]);
+    assert_eq!(encode("ab"), [2, 0, 0, 0, 0, 0, 0, 0, b'a', b'b']);
+    assert_eq!(encode(&Some(1u8)), [1, 1]);
+    assert_eq!(encode(&None::<u8>), [0]);
+    assert_eq!(encode(&Shape::Empty), [0, 0, 0, 0]);
+    assert_eq!(
+        encode(&Shape::Line(Pair(1, -1))),
+        [2, 0, 0, 0, 1, 0, 0xff, 0xff]
+    );
+
+    // Length prefixes keep ("ab", "c") and ("a", "bc") apart
+    assert_ne!(encode(&("ab", "c")), encode(&("a", "bc")));
+    assert_ne!(encode(&[vec![1u8], vec![]]), encode(&[vec![], vec![1u8]]));
+
+    // Sequences hash the same whatever their type
+    let vec = vec![1u16, 2, 3];
+    assert_eq!(encode(&vec), encode(&[1u16, 2, 3]));
+    assert_eq!(encode(&vec), encode(&BTreeSet::from([3u16, 2, 1])));
+    assert_eq!(encode(&vec), encode(&HashSet::<u16>::from_iter([2, 3, 1])));
+
+    // Maps in the order of their keys
+    let entries = [("b", 2), ("a", 1), ("c", 3)];
+    let map: HashMap<&str, i32> = HashMap::from_iter(entries);
+    assert_eq!(encode(&map), encode(&BTreeMap::from_iter(entries)));
+    assert_eq!(encode(&map), encode(&[("a", 1), ("b", 2), ("c", 3)]));
+}
+
+// Digests must never change: a failure here means stored hashes are lost,
+// and `hash::VERSION` must be increased
+#[test]
+fn stable_digests() {
+    assert_eq!(hash::VERSION, 1);
+    assert_eq!(hash::digest(&Structure { x: 10 }), DIGEST_STRUCTURE);
+    assert_eq!(hash::digest(&10i32), DIGEST_STRUCTURE);
+    assert_eq!(hash::digest("hello"), DIGEST_HELLO);
+    assert_eq!(hash::digest(&Shape::Circle { radius: 5 }), DIGEST_CIRCLE);
+    let map = HashMap::from([(1u64, "one".to_string()), (2, "two".to_string())]);
+    assert_eq!(hash::digest(&map), DIGEST_MAP);
+}
+
+const DIGEST_STRUCTURE: u64 = 0x3f02512c8ac6394c;
+const DIGEST_HELLO: u64 = 0x814cea1da628063b;
+const DIGEST_CIRCLE: u64 = 0x21ae737554e11b45;
+const DIGEST_MAP: u64 = 0x08d6be04053b0f07;
+
+// __________________________________________
+// Analysis of hashers
+
+#[test]
+fn analysis() {
+    let options = Options {
+        keys: 4096,
+        avalanche_samples: 200,
+        duration: Duration::from_millis(1),
+        ..Options::default()
+    };
+
+    // `CustomHasher` returns 10 whatever the key
+    let custom = analysis::analyze(
+        "custom",
+        &BuildHasherDefault::<CustomHasher>::default(),
+        &options,
+    );
+    assert_eq!(custom.avalanche.mean, 0.0);
+    assert_eq!(custom.avalanche.worst_bias, 1.0);
+    for distribution in &custom.distributions {
+        assert_eq!(distribution.collisions, 4095);
+        assert!(distribution.z_score > 100.0);
+    }
+
+    let xx = analysis::analyze("xxHash64", &BuildXxHash64::default(), &options);
+    assert!((xx.avalanche.mean - 0.5).abs() < 0.01);
+    assert!(xx.avalanche.worst_bias < 0.4);
+    for distribution in &xx.distributions {
+        assert_eq!(distribution.collisions, 0);
+        assert!(distribution.z_score.abs() < 5.0);
+    }
+    let key_sets: Vec<KeySet> = xx
+        .distributions
+        .iter()
+        .map(|distribution| distribution.key_set)
+        .collect();
+    assert_eq!(key_sets, KeySet::ALL);
+    assert!(xx.throughput.bytes_per_second > 0.0);
+
+    let analyses = [custom, xx];
+    let table = analysis::table(&analyses).to_string();
+    let lines: Vec<&str> = table.lines().collect();
+    assert!(lines[0].starts_with("hasher    avalanche   bias      sequential"));
+    assert!(lines[1].starts_with("custom        0.000  1.000"));
+    assert!(lines[1].contains("(4095)"));
+
+    let json = analysis::json(&analyses).to_string();
+    assert!(json
+        .starts_with(r#"[{"hasher":"custom","avalanche":{"samples":200,"mean":0,"worst_bias":1}"#));
+    assert!(
+        json.contains(r#"{"key_set":"short strings","keys":4096,"buckets":512,"collisions":4095,"#)
+    );
+}
+
+// __________________________________________
+// Probabilistic data structures
+
+#[test]
+fn bloom_filter() {
+    let mut filter = BloomFilter::new(10_000, 0.01);
+    assert_eq!((filter.bits(), filter.hashes()), (95_851, 7));
+    for x in 0..10_000 {
+        filter.insert(&Structure { x });
+    }
+    assert!((0..10_000).all(|x| filter.contains(&Structure { x })));
+    let false_positives = (10_000..110_000)
+        .filter(|&x| filter.contains(&Structure { x }))
+        .count();
+    assert!((500..1500).contains(&false_positives), "{false_positives}");
+    assert!((filter.false_positive_rate() - 0.01).abs() < 0.002);
+
+    // Union, with another hasher
+    let mut odd = BloomFilter::with_hasher(1000, 0.01, BuildFnv1a64::default());
+    let mut even = odd.clone();
+    assert!(even.is_empty());
+    for x in 0..1000 {
+        let filter = if x % 2 == 0 { &mut even } else { &mut odd };
+        assert!(filter.insert(&x));
+        assert!(!filter.insert(&x));
+    }
+    odd.union(&even).unwrap();
+    assert!((0..1000).all(|x| odd.contains(&x)));
+    let small = BloomFilter::with_hasher(10, 0.01, BuildFnv1a64::default());
+    assert_eq!(odd.union(&small), Err(MergeError));
+
+    let bytes = odd.to_bytes();
+    assert_eq!(&bytes[..4], b"BLM1");
+    let decoded = BloomFilter::from_bytes(&bytes, BuildFnv1a64::default()).unwrap();
+    assert_eq!(decoded, odd);
+    assert_eq!(
+        BloomFilter::from_bytes(&bytes[..bytes.len() - 1], BuildFnv1a64::default()),
+        Err(DecodeError::Length {
+            expected: bytes.len(),
+            actual: bytes.len() - 1
+        })
+    );
+    let error = BloomFilter::from_bytes(b"HLL1", BuildFnv1a64::default()).unwrap_err();
+    assert_eq!(error.to_string(), "not a serialized Bloom filter");
+}
+
+#[test]
+fn counting_bloom_filter() {
+    let mut filter = CountingBloomFilter::new(1000, 0.01);
+    for x in 0..1000 {
+        filter.insert(&x);
+    }
+    filter.insert(&0);
+    assert_eq!(filter.count(&0), 2);
+    for x in 0..500 {
+        assert!(filter.remove(&x));
+    }
+    assert!(filter.contains(&0));
+    assert!(filter.remove(&0));
+    assert!(!filter.contains(&0));
+    assert!((500..1000).all(|x| filter.contains(&x)));
+    let false_positives = (1..500).filter(|x| filter.contains(x)).count();
+    assert!(false_positives < 20, "{false_positives}");
+
+    let mut other = CountingBloomFilter::new(1000, 0.01);
+    other.insert(&0);
+    filter.merge(&other).unwrap();
+    assert!(filter.contains(&0));
+
+    let decoded = CountingBloomFilter::from_bytes(&filter.to_bytes(), Default::default());
+    assert_eq!(decoded, Ok(filter));
+}
+
+#[test]
+fn count_min_sketch() {
+    let (epsilon, delta) = (0.001, 0.01);
+    let mut sketch = CountMinSketch::new(epsilon, delta);
+    assert_eq!((sketch.width(), sketch.depth()), (2719, 5));
+    // Item `x` added `x % 100` times
+    for x in 0..10_000u64 {
+        sketch.add(&x, x % 100);
+    }
+    let total = sketch.total();
+    assert_eq!(total, 100 * (0..100).sum::<u64>());
+    let bound = (epsilon * total as f64) as u64;
+    let within = (0..10_000u64)
+        .filter(|x| {
+            let estimate = sketch.estimate(x);
+            estimate >= x % 100 && estimate <= x % 100 + bound
+        })
+        .count();
+    assert!(within as f64 >= 10_000.0 * (1.0 - delta), "{within}");
+
+    let mut other = CountMinSketch::new(epsilon, delta);
+    other.add("event", 5);
+    let mut merged = sketch.clone();
+    merged.merge(&other).unwrap();
+    assert!(merged.estimate("event") >= 5);
+    assert_eq!(merged.total(), total + 5);
+    assert_eq!(
+        merged.merge(&CountMinSketch::new(0.01, delta)),
+        Err(MergeError)
+    );
+
+    let decoded = CountMinSketch::from_bytes(&sketch.to_bytes(), Default::default());
+    assert_eq!(decoded, Ok(sketch));
+}
+
+#[test]
+fn hyper_log_log() {
+    assert_eq!(HyperLogLog::<BuildXxHash64>::precision_for(0.02), 12);
+    let mut first = HyperLogLog::new(12);
+    let mut second = HyperLogLog::new(12);
+    assert_eq!(first.estimate(), 0.0);
+    for x in 0..60_000 {
+        first.insert(&x);
+        second.insert(&(x + 40_000));
+    }
+    let error = |estimate: f64, expected: f64| (estimate - expected).abs() / expected;
+    assert!(error(first.estimate(), 60_000.0) < 0.05);
+    first.merge(&second).unwrap();
+    assert!(error(first.estimate(), 100_000.0) < 0.05);
+    assert_eq!(first.merge(&HyperLogLog::new(10)), Err(MergeError));
+
+    // Small cardinalities
+    let mut small = HyperLogLog::new(12);
+    for word in ["a", "b", "c", "a", "b"] {
+        small.insert(word);
+    }
+    assert_eq!(small.estimate().round(), 3.0);
+
+    let bytes = first.to_bytes();
+    assert_eq!(bytes.len(), 4 + 1 + 4096);
+    assert_eq!(
+        HyperLogLog::from_bytes(&bytes, Default::default()),
+        Ok(first)
+    );
+    assert_eq!(
+        HyperLogLog::from_bytes(b"HLL1\x02", BuildXxHash64::default()),
+        Err(DecodeError::Parameters)
+    );
+}
+
+#[test]
+fn sketch_huge_lengths() {
+    let encoded = |tag: &[u8], rest: &[u8]| {
+        let mut bytes = tag.to_vec();
+        bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        bytes.extend_from_slice(rest);
+        bytes
+    };
+    let filter = encoded(b"BLM1", &7u32.to_le_bytes());
+    assert!(BloomFilter::from_bytes(&filter, BuildFnv1a64::default()).is_err());
+    let filter = encoded(b"CBF1", &7u32.to_le_bytes());
+    assert!(CountingBloomFilter::<BuildXxHash64>::from_bytes(&filter, Default::default()).is_err());
+    let sketch = encoded(b"CMS1", &[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert!(CountMinSketch::<BuildXxHash64>::from_bytes(&sketch, Default::default()).is_err());
+    let log = encoded(b"HLL1", &[]);
+    assert!(HyperLogLog::<BuildXxHash64>::from_bytes(&log, Default::default()).is_err());
+}
+
+// __________________________________________
+// Consistent hashing
+
+#[test]
+fn ring_distribution() {
+    let mut ring = Ring::new(160);
+    for node in 0..10 {
+        ring.add(format!("node-{node}"));
+    }
+    let mut counts: HashMap<&String, usize> = HashMap::new();
+    for key in 0..100_000 {
+        *counts.entry(ring.get(&key).unwrap()).or_default() += 1;
+    }
+    assert_eq!(counts.len(), 10);
+    // 10_000 keys each, within 25%
+    for (node, count) in &counts {
+        assert!((7_500..12_500).contains(count), "{node}: {count}");
+    }
+
+    // A node of weight 3 gets about 3 times the keys
+    let mut ring = Ring::with_hasher(160, BuildSipHasher24::new(1, 2));
+    ring.add("light");
+    ring.add_weighted("heavy", 3);
+    assert_eq!(
+        ring.nodes().collect::<Vec<_>>(),
+        [(&"light", 1), (&"heavy", 3)]
+    );
+    let heavy = (0..100_000)
+        .filter(|key| ring.get(key) == Some(&"heavy"))
+        .count();
+    assert!((70_000..80_000).contains(&heavy), "{heavy}");
+}
+
+#[test]
+fn ring_moves() {
+    let mut ring = Ring::new(100);
+    assert!(ring.get("key").is_none());
+    assert!(ring.add("a").is_empty());
+    ring.add("b");
+    ring.add("c");
+    let owners: Vec<&str> = (0..10_000usize)
+        .map(|key| *ring.get(&key).unwrap())
+        .collect();
+
+    // Only the keys of the moved ranges change node, to the new one
+    let moved = ring.add("d");
+    assert!(!moved.is_empty());
+    assert!(moved
+        .iter()
+        .all(|moved| moved.to == "d" && moved.from != "d"));
+    let mut changed = 0;
+    for (key, &before) in owners.iter().enumerate() {
+        let owner = *ring.get(&key).unwrap();
+        let position = ring.position(&key);
+        let range = moved.iter().find(|moved| moved.range.contains(position));
+        match range {
+            Some(moved) => {
+                assert_eq!((before, owner), (moved.from, moved.to));
+                changed += 1;
+            }
+            None => assert_eq!(before, owner),
+        }
+    }
+    // About a quarter of the keys
+    assert!((1_500..3_500).contains(&changed), "{changed}");
+
+    // Removing it moves them back
+    let back = ring.remove(&"d");
+    let mut reversed: Vec<_> = back.iter().map(|moved| (moved.range, moved.to)).collect();
+    let mut expected: Vec<_> = moved
+        .iter()
+        .map(|moved| (moved.range, moved.from))
+        .collect();
+    reversed.sort_by_key(|(range, _)| range.start);
+    expected.sort_by_key(|(range, _)| range.start);
+    assert_eq!(reversed, expected);
+    assert!((0..10_000).all(|key| *ring.get(&key).unwrap() == owners[key]));
+    assert_eq!(ring.len(), 3);
+}
+
+#[test]
+fn ring_replicas() {
+    let mut ring = Ring::new(50);
+    for node in ["a", "b", "c", "d"] {
+        ring.add(node);
+    }
+    for key in 0..1000 {
+        let replicas = ring.replicas(&key, 3);
+        assert_eq!(replicas.len(), 3);
+        assert_eq!(replicas[0], ring.get(&key).unwrap());
+        assert!(replicas
+            .iter()
+            .all(|node| replicas.iter().filter(|other| *other == node).count() == 1));
+    }
+    assert_eq!(ring.replicas("key", 10).len(), 4);
+}
@@ -55,3 +55,103 @@ fn offset() {
     }
     assert_eq!(mem::offset_of!(Struct, b), 2);
 }
+
+// Registry of types, src/lib/registry.rs
+
+#[derive(Debug, Clone, Default, PartialEq, lib::describe::Describe)]
+struct Registered {
+    a: u16,
+    b: u8,
+}
+
+#[derive(lib::describe::Describe)]
+enum RegisteredEnum {
+    A(std::rc::Rc<u8>),
+}
+
+#[test]
+fn registry() {
+    use lib::registry::{self, Trait};
+
+    let threads: Vec<_> = (0..4)
+        .map(|_| std::thread::spawn(|| lib::register_type!(Registered) as *const _ as usize))
+        .collect();
+    let addresses: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+    assert!(addresses.windows(2).all(|w| w[0] == w[1]));
+
+    let info = registry::get(TypeId::of::<Registered>()).unwrap();
+    assert_eq!(info.description.size, mem::size_of::<Registered>());
+    assert_eq!(
+        info.description.field("b").unwrap().offset,
+        Some(mem::offset_of!(Registered, b))
+    );
+    assert_eq!(
+        info.traits,
+        [
+            Trait::Debug,
+            Trait::Clone,
+            Trait::Default,
+            Trait::PartialEq,
+            Trait::Send,
+            Trait::Sync,
+            Trait::Unpin
+        ]
+    );
+
+    let info = lib::register_type!(RegisteredEnum);
+    assert!(!info.implements(Trait::Send) && info.implements(Trait::Unpin));
+    assert_eq!(
+        registry::by_name(std::any::type_name::<RegisteredEnum>()),
+        Some(info)
+    );
+    assert!(registry::types().any(|info| info.id == TypeId::of::<Registered>()));
+    assert!(registry::get_of::<i32>().is_none());
+}
+
+// Map keyed by type, src/lib/type_map.rs
+
+#[test]
+fn type_map() {
+    use lib::type_map::{CloneSyncTypeMap, CloneTypeMap, SyncTypeMap, TypeIdHasher};
+    use lib::TypeMap;
+    use std::hash::{Hash, Hasher};
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    let mut map = TypeMap::new();
+    assert!(map.insert(Rc::new(1)).is_none());
+    assert!(map.contains::<Rc<i32>>());
+    map.entry::<Vec<u8>>().or_insert_with(|| vec![0]).push(1);
+    map.entry::<Vec<u8>>()
+        .and_modify(|v| v.push(2))
+        .or_default();
+    assert_eq!(map.get::<Vec<u8>>(), Some(&vec![0, 1, 2]));
+    map.get_mut::<Vec<u8>>().unwrap().clear();
+    assert_eq!(map.remove::<Vec<u8>>(), Some(vec![]));
+    assert!(map.get::<Vec<u8>>().is_none());
+    assert_eq!(map.len(), 1);
+
+    let mut map = SyncTypeMap::default();
+    map.insert(Arc::new(String::from("shared")));
+    let map = Arc::new(map);
+    let thread_map = Arc::clone(&map);
+    let value = std::thread::spawn(move || thread_map.get::<Arc<String>>().unwrap().len());
+    assert_eq!(value.join().unwrap(), 6);
+
+    let mut map = CloneTypeMap::default();
+    map.insert(Rc::new(1));
+    map.insert(10u8);
+    let mut copy = map.clone();
+    *copy.get_mut::<u8>().unwrap() += 1;
+    assert_eq!((map.get::<u8>(), copy.get::<u8>()), (Some(&10), Some(&11)));
+    assert_eq!(Rc::strong_count(map.get::<Rc<i32>>().unwrap()), 2);
+
+    let mut map = CloneSyncTypeMap::default();
+    map.insert(String::from("a"));
+    let copy = std::thread::spawn(move || map.clone()).join().unwrap();
+    assert_eq!(copy.get::<String>().map(String::as_str), Some("a"));
+
+    let mut hasher = TypeIdHasher::default();
+    TypeId::of::<u8>().hash(&mut hasher);
+    assert_ne!(hasher.finish(), 0);
+}
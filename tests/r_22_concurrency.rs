@@ -1,6 +1,15 @@
+use lib::cancel::{CancelError, CancellationToken};
+use lib::chan::{
+    self, RecvCancelError, RecvError, RecvTimeoutError, SendError, SendTimeoutError, TryRecvError,
+    TrySendError,
+};
+use lib::par::{self, ParallelSlice};
+use lib::pool::{JoinError, ThreadPool};
+use lib::sync::{self, PotentialDeadlock};
+use std::sync::atomic::{self, AtomicUsize};
 use std::sync::*;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 /*
     Send: Types that can be transferred across thread boundaries.
@@ -114,3 +123,595 @@ fn lazy_lock() {
 
     assert_eq!(*LOCK, 1);
 }
+
+// ____________________________________________________________
+// Thread pool
+
+#[test]
+fn thread_pool_panics() {
+    // A single worker runs the jobs in order
+    let pool = ThreadPool::with_name(1, "worker");
+    let order = Arc::new(Mutex::new(Vec::new()));
+    // All queued before joining any
+    let handles: Vec<_> = (0..8)
+        .map(|index| {
+            let order = order.clone();
+            pool.spawn(move || {
+                thread::sleep(Duration::from_millis(1));
+                order.lock().unwrap().push(index);
+                (index, thread::current().name().unwrap().to_string())
+            })
+        })
+        .collect();
+    for (index, handle) in handles.into_iter().enumerate() {
+        assert_eq!(handle.join().unwrap(), (index, "worker-0".to_string()));
+    }
+    assert_eq!(*order.lock().unwrap(), (0..8).collect::<Vec<_>>());
+
+    // Panics don't stop the workers
+    for _ in 0..4 {
+        pool.execute(|| panic!("job failed"));
+    }
+    let error = pool
+        .spawn(|| panic!("spawned job failed"))
+        .join()
+        .unwrap_err();
+    assert!(matches!(error, JoinError::Panicked(_)));
+    assert_eq!(error.panic_message(), Some("spawned job failed"));
+    let values: Vec<i32> = (0..10)
+        .map(|x| pool.spawn(move || lib::increment(x)))
+        .map(|handle| handle.join().unwrap())
+        .collect();
+    assert_eq!(values, (1..11).collect::<Vec<_>>());
+    assert_eq!(pool.panicked(), 5);
+    assert_eq!(pool.threads(), 1);
+}
+
+#[test]
+fn thread_pool_shutdown() {
+    // Graceful: the queued jobs run, in order with a single worker
+    let pool = ThreadPool::new(1);
+    let order = Arc::new(Mutex::new(Vec::new()));
+    for x in 0..5 {
+        let order = order.clone();
+        pool.execute(move || {
+            thread::sleep(Duration::from_millis(10));
+            order.lock().unwrap().push(x);
+        });
+    }
+    pool.shutdown();
+    assert_eq!(*order.lock().unwrap(), [0, 1, 2, 3, 4]);
+
+    // Immediate: the running job finishes, the queued ones are dropped
+    let pool = ThreadPool::new(1);
+    let (started, wait_started) = mpsc::channel();
+    let running = pool.spawn(move || {
+        started.send(()).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        "finished"
+    });
+    let queued: Vec<_> = (0..3).map(|x| pool.spawn(move || x)).collect();
+    wait_started.recv().unwrap();
+    assert_eq!(pool.queued(), 3);
+    assert_eq!(pool.shutdown_now(), 3);
+    assert_eq!(running.join().unwrap(), "finished");
+    for handle in queued {
+        assert!(matches!(handle.join(), Err(JoinError::Cancelled)));
+    }
+
+    // Dropping the pool waits for its jobs
+    let done = Arc::new(AtomicUsize::new(0));
+    {
+        let pool = ThreadPool::new(3);
+        for _ in 0..6 {
+            let done = done.clone();
+            pool.execute(move || {
+                thread::sleep(Duration::from_millis(10));
+                done.fetch_add(1, atomic::Ordering::SeqCst);
+            });
+        }
+    }
+    assert_eq!(done.load(atomic::Ordering::SeqCst), 6);
+}
+
+// ____________________________________________________________
+// Bounded channels
+
+#[test]
+fn bounded_channel() {
+    // `message_passing` with cloned receivers instead of a mutex
+    let (sender, receiver) = chan::bounded(2);
+    let producers: Vec<_> = (0..2)
+        .map(|producer| {
+            let sender = sender.clone();
+            thread::spawn(move || {
+                for x in 0..50 {
+                    sender.send(producer * 100 + x).unwrap();
+                }
+            })
+        })
+        .collect();
+    drop(sender);
+    let consumers: Vec<_> = (0..2)
+        .map(|_| {
+            let receiver = receiver.clone();
+            thread::spawn(move || receiver.iter().collect::<Vec<i32>>())
+        })
+        .collect();
+    drop(receiver);
+    for producer in producers {
+        producer.join().unwrap();
+    }
+    let mut received: Vec<i32> = consumers
+        .into_iter()
+        .flat_map(|consumer| consumer.join().unwrap())
+        .collect();
+    received.sort();
+    let expected: Vec<i32> = (0..50).chain(100..150).collect();
+    assert_eq!(received, expected);
+}
+
+#[test]
+fn bounded_channel_backpressure() {
+    let (sender, receiver) = chan::bounded(2);
+    sender.try_send(1).unwrap();
+    sender.send(2).unwrap();
+    assert_eq!(sender.try_send(3), Err(TrySendError::Full(3)));
+    let start = Instant::now();
+    let error = sender
+        .send_timeout(3, Duration::from_millis(50))
+        .unwrap_err();
+    assert!(matches!(error, SendTimeoutError::Timeout(3)));
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert_eq!((sender.len(), sender.capacity()), (2, 2));
+
+    // A blocked sender resumes once a message is received
+    let blocked = thread::spawn({
+        let sender = sender.clone();
+        move || sender.send(3)
+    });
+    thread::sleep(Duration::from_millis(20));
+    assert!(!blocked.is_finished());
+    assert_eq!(receiver.recv(), Ok(1));
+    blocked.join().unwrap().unwrap();
+    assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [2, 3]);
+    assert_eq!(
+        receiver.recv_timeout(Duration::from_millis(10)),
+        Err(RecvTimeoutError::Timeout)
+    );
+
+    // Timeouts too far to be represented never expire
+    sender.send_timeout(4, Duration::MAX).unwrap();
+    assert_eq!(receiver.recv_timeout(Duration::MAX), Ok(4));
+    sender.send(5).unwrap();
+    let received = lib::select! {
+        recv(receiver) -> message => message,
+        default(Duration::MAX) => unreachable!(),
+    };
+    assert_eq!(received, Ok(5));
+}
+
+#[test]
+fn bounded_channel_disconnection() {
+    // Messages sent before the senders are dropped are still received
+    let (sender, receiver) = chan::bounded(4);
+    sender.send("last").unwrap();
+    drop(sender);
+    assert_eq!(receiver.recv(), Ok("last"));
+    assert_eq!(receiver.recv(), Err(RecvError));
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+    assert_eq!(
+        receiver.recv_timeout(Duration::from_secs(1)),
+        Err(RecvTimeoutError::Disconnected)
+    );
+    assert_eq!(
+        receiver.recv_timeout(Duration::MAX),
+        Err(RecvTimeoutError::Disconnected)
+    );
+
+    // Sending fails once the receivers are dropped, even to a waiting sender
+    let (sender, receiver) = chan::bounded(1);
+    sender.send(1).unwrap();
+    let blocked = thread::spawn(move || sender.send(2));
+    thread::sleep(Duration::from_millis(20));
+    drop(receiver);
+    assert_eq!(blocked.join().unwrap(), Err(SendError(2)));
+}
+
+#[test]
+fn select() {
+    let (numbers, number_receiver) = chan::bounded::<i32>(1);
+    let (words, word_receiver) = chan::bounded::<String>(1);
+
+    let (done, finished) = mpsc::channel();
+    let producer = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        words.send("hello".to_string()).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        numbers.send(42).unwrap();
+        // Both senders are dropped together once the messages are received
+        finished.recv().unwrap();
+    });
+    let mut received = Vec::new();
+    let mut select = || {
+        lib::select! {
+            recv(number_receiver) -> number => received.push(format!("number {number:?}")),
+            recv(word_receiver) -> word => received.push(format!("word {word:?}")),
+        }
+    };
+    select();
+    select();
+    // A disconnected receiver doesn't starve the other
+    done.send(()).unwrap();
+    producer.join().unwrap();
+    select();
+    select();
+    // Starting from either receiver
+    received[2..].sort();
+    assert_eq!(
+        received,
+        [
+            "word Ok(\"hello\")",
+            "number Ok(42)",
+            "number Err(RecvError)",
+            "word Err(RecvError)"
+        ]
+    );
+
+    let (_sender, receiver) = chan::bounded::<i32>(1);
+    let start = Instant::now();
+    let timed_out = lib::select! {
+        recv(receiver) -> message => false,
+        default(Duration::from_millis(30)) => true,
+    };
+    assert!(timed_out && start.elapsed() >= Duration::from_millis(30));
+}
+
+// ____________________________________________________________
+// Data parallelism
+
+#[test]
+fn parallel_slices() {
+    let numbers: Vec<i32> = (0..100_000).collect();
+    let incremented = numbers.par_map(|&x| lib::increment(x));
+    assert_eq!(incremented, (1..100_001).collect::<Vec<_>>());
+    let multiples: Vec<&i32> = numbers.par_filter(|x| *x % 7 == 0);
+    assert_eq!(
+        multiples,
+        numbers.iter().filter(|x| *x % 7 == 0).collect::<Vec<_>>()
+    );
+
+    let total = AtomicUsize::new(0);
+    numbers.par_for_each(|&x| {
+        total.fetch_add(x as usize, atomic::Ordering::Relaxed);
+    });
+    assert_eq!(total.into_inner(), 4_999_950_000);
+
+    // Concatenation is not commutative, the order is kept
+    let words: Vec<String> = (0..1000).map(|x| x.to_string()).collect();
+    let concatenated = words.par_reduce(|a, b| a + &b).unwrap();
+    assert_eq!(concatenated, words.concat());
+    assert_eq!(Vec::<String>::new().par_reduce(|a, b| a + &b), None);
+
+    // Chunks are balanced between threads: the thread of the slow first
+    // items takes fewer of them than the quarter of a split in 4 parts
+    let items: Vec<u64> = (0..64).collect();
+    let threads = par::with_threads(4, || {
+        items.par_map(|&x| {
+            let delay = if x < 8 { 20 } else { 1 };
+            thread::sleep(Duration::from_millis(delay));
+            thread::current().id()
+        })
+    });
+    let slow_items = threads.iter().filter(|&&id| id == threads[0]).count();
+    assert!(slow_items < items.len() / 4, "{slow_items}");
+}
+
+#[test]
+fn parallel_threads() {
+    let items: Vec<usize> = (0..1000).collect();
+    let thread_ids = |threads| {
+        par::with_threads(threads, || {
+            let ids = Mutex::new(std::collections::HashSet::new());
+            items.par_for_each(|_| {
+                ids.lock().unwrap().insert(thread::current().id());
+                thread::sleep(Duration::from_micros(100));
+            });
+            ids.into_inner().unwrap()
+        })
+    };
+    let ids = thread_ids(1);
+    assert_eq!(
+        ids.into_iter().collect::<Vec<_>>(),
+        [thread::current().id()]
+    );
+    let ids = thread_ids(3);
+    assert!((2..=3).contains(&ids.len()), "{}", ids.len());
+    assert!(!ids.contains(&thread::current().id()));
+
+    par::with_threads(5, || assert_eq!(par::threads(), 5));
+    assert_eq!(
+        par::threads(),
+        thread::available_parallelism().map_or(1, usize::from)
+    );
+}
+
+#[test]
+fn parallel_panics() {
+    let items: Vec<i32> = (0..10_000).collect();
+    let processed = AtomicUsize::new(0);
+    let result = std::panic::catch_unwind(|| {
+        par::with_threads(4, || {
+            items.par_for_each(|&x| {
+                if x == 100 {
+                    panic!("item {x} failed");
+                }
+                processed.fetch_add(1, atomic::Ordering::Relaxed);
+                thread::sleep(Duration::from_micros(10));
+            })
+        })
+    });
+    let payload = result.unwrap_err();
+    assert_eq!(payload.downcast_ref::<String>().unwrap(), "item 100 failed");
+    // The other threads stopped early
+    assert!(processed.into_inner() < 9_999);
+}
+
+// ____________________________________________________________
+// Cancellation
+
+#[test]
+fn cancellation_tokens() {
+    let token = CancellationToken::new();
+    let child = token.child();
+    let grandchild = child.child();
+    let clone = child.clone();
+    assert!(!grandchild.is_cancelled());
+
+    // A child's cancellation doesn't reach its parent
+    child.cancel();
+    assert_eq!(clone.check(), Err(CancelError::Cancelled));
+    assert_eq!(grandchild.check(), Err(CancelError::Cancelled));
+    assert!(!token.is_cancelled());
+    let other = token.child();
+    token.cancel();
+    assert!(other.is_cancelled());
+    assert!(token.child().is_cancelled());
+
+    let called = Arc::new(AtomicUsize::new(0));
+    let token = CancellationToken::new();
+    let count = |called: &Arc<AtomicUsize>| {
+        let called = called.clone();
+        move || {
+            called.fetch_add(1, atomic::Ordering::SeqCst);
+        }
+    };
+    let registration = token.on_cancel(count(&called));
+    drop(token.on_cancel(count(&called)));
+    token.cancel();
+    token.cancel();
+    assert_eq!(called.load(atomic::Ordering::SeqCst), 1);
+    drop(registration);
+    let _registration = token.on_cancel(count(&called));
+    assert_eq!(called.load(atomic::Ordering::SeqCst), 2);
+}
+
+#[test]
+fn cancellation_deadlines() {
+    // Deadlines not expected to pass during the test are long
+    let token = CancellationToken::with_timeout(Duration::from_secs(60));
+    let child = token.child_with_timeout(Duration::from_secs(120));
+    assert_eq!(child.deadline(), token.deadline());
+    let short = token.child_with_timeout(Duration::from_millis(10));
+    assert!(short.deadline() < token.deadline());
+
+    assert_eq!(
+        short.sleep(Duration::from_secs(60)),
+        Err(CancelError::DeadlineExceeded)
+    );
+    assert!(Instant::now() >= short.deadline().unwrap());
+    assert!(!token.is_cancelled());
+    assert_eq!(token.sleep(Duration::from_millis(1)), Ok(()));
+
+    let token = CancellationToken::with_timeout(Duration::from_millis(20));
+    assert_eq!(token.child().wait(), CancelError::DeadlineExceeded);
+    assert!(Instant::now() >= token.deadline().unwrap());
+    assert_eq!(token.check(), Err(CancelError::DeadlineExceeded));
+    assert_eq!(token.remaining(), Some(Duration::ZERO));
+
+    // Timeouts too far to be represented are no deadline
+    let token = CancellationToken::with_timeout(Duration::MAX);
+    assert_eq!(token.deadline(), None);
+    assert_eq!(token.child_with_timeout(Duration::MAX).deadline(), None);
+}
+
+#[test]
+fn cancellation_wakes_up() {
+    let token = CancellationToken::new();
+    let cancel_later = |token: &CancellationToken| {
+        let token = token.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            token.cancel();
+        })
+    };
+
+    // As `condvar`, but the notification never comes
+    let pair = Arc::new((Mutex::new(false), Condvar::new()));
+    let canceller = cancel_later(&token);
+    let started = token.wait_while(&pair, |started| !*started);
+    assert_eq!(started.err(), Some(CancelError::Cancelled));
+    canceller.join().unwrap();
+
+    // Cancelled while holding the mutex, as when setting the state too
+    let token = CancellationToken::new();
+    let waiter = thread::spawn({
+        let (pair, token) = (pair.clone(), token.clone());
+        move || token.wait_while(&pair, |started| !*started).map(|_| ())
+    });
+    thread::sleep(Duration::from_millis(20));
+    {
+        let _started = pair.0.lock().unwrap();
+        token.cancel();
+    }
+    assert_eq!(waiter.join().unwrap(), Err(CancelError::Cancelled));
+    *pair.0.lock().unwrap() = true;
+    assert!(CancellationToken::new()
+        .wait_while(&pair, |started| !*started)
+        .is_ok());
+
+    let token = CancellationToken::new();
+    let canceller = cancel_later(&token);
+    let start = Instant::now();
+    assert_eq!(
+        token.sleep(Duration::from_secs(60)),
+        Err(CancelError::Cancelled)
+    );
+    assert!(start.elapsed() < Duration::from_secs(60));
+    canceller.join().unwrap();
+    let token = CancellationToken::new();
+    let canceller = cancel_later(&token);
+    assert_eq!(token.sleep(Duration::MAX), Err(CancelError::Cancelled));
+    canceller.join().unwrap();
+
+    let (sender, receiver) = chan::bounded::<i32>(1);
+    let token = CancellationToken::new();
+    sender.send(1).unwrap();
+    assert_eq!(receiver.recv_cancellable(&token), Ok(1));
+    let canceller = cancel_later(&token);
+    assert_eq!(
+        receiver.recv_cancellable(&token),
+        Err(RecvCancelError::Cancelled(CancelError::Cancelled))
+    );
+    canceller.join().unwrap();
+    let token = CancellationToken::with_timeout(Duration::from_millis(10));
+    assert_eq!(
+        receiver.recv_cancellable(&token),
+        Err(RecvCancelError::Cancelled(CancelError::DeadlineExceeded))
+    );
+    drop(sender);
+    let token = CancellationToken::new();
+    assert_eq!(
+        receiver.recv_cancellable(&token),
+        Err(RecvCancelError::Disconnected)
+    );
+}
+
+// ____________________________________________________________
+// Lock order
+
+/// Records the potential deadlocks found while `f` runs on this thread.
+fn find_deadlocks(reports: &Arc<Mutex<Vec<PotentialDeadlock>>>, f: impl FnOnce()) {
+    let reports = reports.clone();
+    sync::with_reporter(
+        move |deadlock| reports.lock().unwrap().push(deadlock.clone()),
+        f,
+    );
+}
+
+#[cfg(debug_assertions)]
+#[test]
+fn lock_order() {
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let a = sync::Mutex::new(1);
+    let b = sync::RwLock::new(2);
+    let line = line!();
+    find_deadlocks(&reports, || {
+        for _ in 0..2 {
+            {
+                let a = a.lock().unwrap();
+                let b = b.read().unwrap();
+                assert!(*a < *b);
+            }
+            let mut b = b.write().unwrap();
+            *b += *a.lock().unwrap();
+        }
+    });
+
+    // Reported once, while no deadlock happened
+    let reports = reports.lock().unwrap();
+    assert_eq!(reports.len(), 1);
+    let acquisitions = &reports[0].acquisitions;
+    assert_eq!(acquisitions.len(), 2);
+    let (closing, first) = (&acquisitions[0], &acquisitions[1]);
+    assert_eq!((closing.lock, closing.held), (first.held, first.lock));
+    assert_eq!(closing.location.line(), line + 9);
+    assert_eq!(first.location.line(), line + 5);
+    assert_eq!(closing.thread, "lock_order");
+    assert!(reports[0].to_string().starts_with(&format!(
+        "potential deadlock, locks acquired in a cycle\n\n\
+         lock #{} acquired while holding lock #{} at {}:{}:",
+        closing.lock,
+        closing.held,
+        file!(),
+        line + 9
+    )));
+}
+
+#[cfg(debug_assertions)]
+#[test]
+fn lock_order_threads() {
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let locks: Arc<[sync::Mutex<()>; 3]> = Arc::new(Default::default());
+    // Each thread locks two locks, in turn not to deadlock
+    for first in 0..3 {
+        let reports = reports.clone();
+        let locks = locks.clone();
+        thread::Builder::new()
+            .name(format!("locker-{first}"))
+            .spawn(move || {
+                find_deadlocks(&reports, || {
+                    let _first = locks[first].lock().unwrap();
+                    let _second = locks[(first + 1) % 3].lock().unwrap();
+                })
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    let reports = reports.lock().unwrap();
+    assert_eq!(reports.len(), 1);
+    let acquisitions = &reports[0].acquisitions;
+    let threads: Vec<&str> = acquisitions.iter().map(|a| a.thread.as_str()).collect();
+    assert_eq!(threads, ["locker-2", "locker-0", "locker-1"]);
+    for (index, acquisition) in acquisitions.iter().enumerate() {
+        let next = &acquisitions[(index + 1) % 3];
+        assert_eq!(acquisition.lock, next.held);
+    }
+}
+
+#[test]
+fn lock_order_consistent() {
+    // The producer and consumer of `condvar` with a queue, and statistics
+    // always locked after the queue
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let queue = Arc::new((sync::Mutex::new(Vec::new()), sync::Condvar::new()));
+    let received = Arc::new(sync::Mutex::new(0));
+
+    let consumer = {
+        let (reports, queue, received) = (reports.clone(), queue.clone(), received.clone());
+        thread::spawn(move || {
+            find_deadlocks(&reports, || loop {
+                let (lock, condvar) = &*queue;
+                let mut items = condvar.wait_while(lock.lock().unwrap(), |items| items.is_empty());
+                let items = items.as_mut().unwrap();
+                let item = items.pop().unwrap();
+                *received.lock().unwrap() += 1;
+                if item == 0 {
+                    break;
+                }
+            })
+        })
+    };
+    find_deadlocks(&reports, || {
+        for item in (0..100).rev() {
+            let (lock, condvar) = &*queue;
+            lock.lock().unwrap().insert(0, item);
+            condvar.notify_one();
+        }
+    });
+    consumer.join().unwrap();
+    assert_eq!(*received.lock().unwrap(), 100);
+    assert!(reports.lock().unwrap().is_empty());
+}
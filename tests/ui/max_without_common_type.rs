@@ -0,0 +1,3 @@
+fn main() {
+    lib::max!(1usize, 1u32);
+}
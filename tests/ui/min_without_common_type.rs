@@ -0,0 +1,3 @@
+fn main() {
+    lib::min!(1u64, 1.0f64);
+}
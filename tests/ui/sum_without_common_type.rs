@@ -0,0 +1,3 @@
+fn main() {
+    lib::sum!(1i128, 1.0f32);
+}